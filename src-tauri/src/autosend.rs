@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+use tokio::sync::{watch, RwLock};
+use tokio::time::{Duration, Instant, MissedTickBehavior};
+
+use crate::messages::{send_message, MessageCodecs, OutgoingMessage};
+use crate::producers::ReportingProducer;
+use crate::templates::render_message;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum TimeUnit {
+    Hours,
+    Minutes,
+    Seconds,
+    Milliseconds,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutosendTime {
+    pub time_unit: TimeUnit,
+    pub value: u64,
+}
+
+impl AutosendTime {
+    pub fn to_duration(&self) -> Duration {
+        match self.time_unit {
+            TimeUnit::Hours => Duration::from_secs(self.value * 60 * 60),
+            TimeUnit::Minutes => Duration::from_secs(self.value * 60),
+            TimeUnit::Seconds => Duration::from_secs(self.value),
+            TimeUnit::Milliseconds => Duration::from_millis(self.value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum AutosendRate {
+    /// A message every interval, even when the previous one is not delivered yet
+    FixedRate,
+    /// Wait the interval after every delivery
+    FixedDelay,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutosendOptions {
+    pub interval: AutosendTime,
+    /// Runs until stopped or until the messages limit when not set
+    pub duration: Option<AutosendTime>,
+    pub max_messages: Option<u64>,
+    pub rate: Option<AutosendRate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Autosend {
+    pub topic: String,
+    pub headers: Option<HashMap<String, Option<String>>>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub options: AutosendOptions,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AutosendStatus {
+    pub id: String,
+    pub autosend: Autosend,
+    pub sent: u64,
+    pub failed: u64,
+    pub started_at: i64,
+    pub ends_at: Option<i64>,
+    pub finished: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AutosendFailure {
+    /// Position of the message in the autosend, starting from 1
+    pub sequence: u64,
+    pub error: String,
+}
+
+/// Autosend running in the background, stopped when dropped from the registry
+pub struct AutosendJob {
+    status: Arc<StdRwLock<AutosendStatus>>,
+    stop: watch::Sender<bool>,
+}
+
+impl AutosendJob {
+    pub fn status(&self) -> AutosendStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn stop(&self) {
+        let _ = self.stop.send(true);
+    }
+}
+
+pub type AutosendJobs = Arc<RwLock<HashMap<String, AutosendJob>>>;
+
+/// Start sending the autosend message in the background, the job removes itself from the
+/// registry when finished. Status is emitted as `onAutosend-{id}` and every failed message as
+/// `onAutosendFailure-{id}`
+pub async fn start_autosend(
+    window: Window,
+    producer: ReportingProducer,
+    autosend: Autosend,
+    jobs: AutosendJobs,
+    id: String,
+) -> Result<(), String> {
+    if jobs.read().await.contains_key(&id) {
+        return Err(format!("Autosend {} already running", id));
+    }
+
+    let interval = autosend.options.interval.to_duration();
+    if interval.is_zero() {
+        return Err("Autosend interval must be greater than zero".into());
+    }
+
+    // Report template errors right away instead of failing every message
+    render_message(
+        autosend.headers.as_ref(),
+        autosend.key.as_deref(),
+        autosend.value.as_deref(),
+        1,
+    )?;
+
+    let started_at = now_millis()?;
+    let duration = autosend
+        .options
+        .duration
+        .as_ref()
+        .map(|duration| duration.to_duration());
+    let status = Arc::new(StdRwLock::new(AutosendStatus {
+        id: id.clone(),
+        autosend: autosend.clone(),
+        sent: 0,
+        failed: 0,
+        started_at,
+        ends_at: duration.map(|duration| started_at + duration.as_millis() as i64),
+        finished: false,
+    }));
+
+    let (stop, mut stopped) = watch::channel(false);
+    jobs.write().await.insert(
+        id.clone(),
+        AutosendJob {
+            status: status.clone(),
+            stop,
+        },
+    );
+
+    tokio::spawn(async move {
+        let deadline = duration.map(|duration| Instant::now() + duration);
+        let rate = autosend.options.rate.unwrap_or(AutosendRate::FixedRate);
+
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut sequence = 0;
+        let mut deliveries = vec![];
+        loop {
+            let wait = async {
+                match rate {
+                    AutosendRate::FixedRate => ticker.tick().await,
+                    AutosendRate::FixedDelay if sequence == 0 => Instant::now(),
+                    AutosendRate::FixedDelay => {
+                        tokio::time::sleep(interval).await;
+                        Instant::now()
+                    }
+                }
+            };
+            let expire = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            let next_tick = tokio::select! {
+                next_tick = wait => next_tick,
+                _ = expire => break,
+                _ = stopped.changed() => break,
+            };
+
+            if *stopped.borrow() || deadline.map_or(false, |deadline| next_tick >= deadline) {
+                break;
+            }
+            if let Some(max_messages) = autosend.options.max_messages {
+                if sequence >= max_messages {
+                    break;
+                }
+            }
+            sequence += 1;
+
+            let delivery = send_autosend_message(
+                window.clone(),
+                producer.clone(),
+                autosend.clone(),
+                status.clone(),
+                sequence,
+            );
+            match rate {
+                AutosendRate::FixedRate => deliveries.push(tokio::spawn(delivery)),
+                AutosendRate::FixedDelay => delivery.await,
+            }
+        }
+
+        for delivery in deliveries {
+            let _ = delivery.await;
+        }
+
+        let final_status = {
+            let mut status = status.write().unwrap();
+            status.finished = true;
+            status.clone()
+        };
+        window
+            .emit(
+                &format!("onAutosend-{}", final_status.id),
+                final_status.clone(),
+            )
+            .unwrap();
+
+        jobs.write().await.remove(&final_status.id);
+    });
+
+    Ok(())
+}
+
+async fn send_autosend_message(
+    window: Window,
+    producer: ReportingProducer,
+    autosend: Autosend,
+    status: Arc<StdRwLock<AutosendStatus>>,
+    sequence: u64,
+) {
+    let result = match render_message(
+        autosend.headers.as_ref(),
+        autosend.key.as_deref(),
+        autosend.value.as_deref(),
+        sequence,
+    ) {
+        Ok(message) => {
+            let codecs = MessageCodecs {
+                schema_registry: None,
+                protobuf: None,
+            };
+            let message = OutgoingMessage {
+                topic: autosend.topic.clone(),
+                headers: message.headers,
+                key: message.key,
+                value: message.value,
+                key_format: None,
+                value_format: None,
+                partition: None,
+            };
+            send_message(&producer, message, codecs).await
+        }
+        Err(err) => Err(err),
+    };
+
+    let current_status = {
+        let mut status = status.write().unwrap();
+        match result {
+            Ok(_) => status.sent += 1,
+            Err(_) => status.failed += 1,
+        }
+        status.clone()
+    };
+
+    if let Err(error) = result {
+        window
+            .emit(
+                &format!("onAutosendFailure-{}", current_status.id),
+                AutosendFailure { sequence, error },
+            )
+            .unwrap();
+    }
+    window
+        .emit(&format!("onAutosend-{}", current_status.id), current_status)
+        .unwrap();
+}
+
+fn now_millis() -> Result<i64, String> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Invalid system time: {}", err.to_string()))?
+        .as_millis() as i64)
+}
@@ -3,16 +3,26 @@
  */
 use jfs::Store;
 use kafka_panel::{
-    commit_latest_offsets, create_connections, create_topic, delete_from_store, delete_group,
-    delete_topic, get_all_from_store, get_env, get_from_store, get_groups_from_topic, get_topics,
-    get_topics_state, get_topics_watermark, listen_messages, logs, save_in_store,
-    seek_earliest_offsets, send_message, Environment, Extras, GroupState, KafkaGroupResponse,
-    SaslConfig, TopicResponse, KafkaState, StorageState,
+    commit_latest_offsets, copy_messages, create_connections, create_topic, delete_from_store,
+    delete_group, delete_topic, export_messages, get_all_from_store, get_decoder_rules, get_env,
+    get_from_store, get_groups_from_topic, get_previous_page, get_topics, get_topics_state,
+    get_topics_watermark, import_messages, listen_messages, logs, materialize_topic, request_reply,
+    save_in_store, search_messages, seek_earliest_offsets, send_message, send_transaction,
+    start_autosend, tombstone_keys, truncate_topic, Autosend, AutosendStatus, ConnectionConfig,
+    CopyOptions, CopyProgress, DeletedRecords, DeliveryReport, Environment, ExportOptions, Extras,
+    GroupState, ImportOptions, ImportProgress, KafkaGroupResponse, KafkaState, ListenOptions,
+    ListenSession, MaterializeOptions, MaterializedTopic, MessageCodecs, MessageFilter,
+    MessageFormat, MessageMatcher, MessagesPage, OutgoingMessage, PartitionCursor, PayloadEncoding,
+    ProducerOptions, ProtobufRegistry, ReplyOptions, ReportingProducers, RoundTrip,
+    SchemaRegistryClient, SearchProgress, StorageState, TargetConnection, TombstoneOptions,
+    TombstoneReport, TopicDecoders, TopicResponse, TransactionMessage, TransactionOutcome,
+    TransactionReport, TransactionalProducer, TruncateBefore,
 };
 use rdkafka::consumer::{Consumer, StreamConsumer};
 use serde_json::Value;
 use std::{
     collections::{BTreeMap, HashMap},
+    sync::Arc,
     time::Duration,
 };
 use tauri::{State, Window};
@@ -20,11 +30,9 @@ use tauri::{State, Window};
 #[tauri::command]
 pub async fn set_connection_command<'a>(
     kafka: State<'a, KafkaState>,
-    brokers: Vec<String>,
-    group_id: String,
-    sasl: Option<SaslConfig>,
+    connection: ConnectionConfig,
 ) -> Result<(), String> {
-    let connections = create_connections(brokers, group_id, sasl).await?;
+    let connections = create_connections(connection).await?;
 
     // Test the connection
     let _ = connections
@@ -36,6 +44,13 @@ pub async fn set_connection_command<'a>(
     *kafka.admin.write().await = Some(connections.admin);
     *kafka.consumer.write().await = Some(connections.consumer);
     *kafka.producer.write().await = Some(connections.producer);
+    *kafka.reporting_producers.write().await = Some(Arc::new(ReportingProducers::new(
+        connections.producer_config.clone(),
+    )));
+    *kafka.producer_config.write().await = Some(connections.producer_config);
+    *kafka.transactional_producer.lock().await = None;
+    *kafka.schema_registry.write().await = connections.schema_registry.map(Arc::new);
+    *kafka.protobuf.write().await = connections.protobuf.map(Arc::new);
 
     Ok(())
 }
@@ -189,38 +204,440 @@ pub async fn delete_topic_command<'a>(
     delete_topic(admin, topic_name).await
 }
 
+#[tauri::command]
+pub async fn truncate_topic_command<'a>(
+    state: State<'a, KafkaState>,
+    topic_name: String,
+    partitions: Option<Vec<i32>>,
+    before: TruncateBefore,
+) -> Result<Vec<DeletedRecords>, String> {
+    let binding = state.admin.read().await;
+    let admin = match *binding {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x,
+    };
+
+    let binding = state.consumer.read().await;
+    let consumer = match *binding {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x,
+    };
+
+    truncate_topic(admin, consumer, topic_name, partitions, before).await
+}
+
 #[tauri::command]
 pub async fn listen_messages_command<'a>(
     window: Window,
     state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
     topic: String,
-    messages_number: i64,
+    options: ListenOptions,
     id: String,
 ) -> Result<(), String> {
-    let binding = state.consumer.read().await;
-    let consumer = match *binding {
+    // No guard is held while listening, set_connection must not wait for the session to end
+    let common_config = match *state.common_config.read().await {
         None => return Err("Connection not set".into()),
-        Some(ref x) => x,
+        Some(ref x) => x.clone(),
     };
 
-    listen_messages(window, consumer, topic, messages_number, id).await
+    // Every session gets its own consumer so that the assignments of different views do not
+    // overwrite each other
+    let mut consumer_config = common_config;
+    consumer_config
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest");
+    if let Some(isolation_level) = options.isolation_level {
+        consumer_config.set("isolation.level", isolation_level.as_config());
+    }
+    let consumer: StreamConsumer = consumer_config
+        .create()
+        .map_err(|err| format!("Error creating consumer connection: {}", err.to_string()))?;
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, options.encoding)?;
+
+    let keep_listening = {
+        let mut sessions = state.listen_sessions.write().await;
+        if sessions.contains_key(&id) {
+            return Err(format!("Session {} is already listening", id));
+        }
+        let session = ListenSession::new(window.label().to_owned());
+        let keep_listening = session.keep_listening();
+        sessions.insert(id.clone(), session);
+        keep_listening
+    };
+
+    let result = listen_messages(
+        window,
+        &consumer,
+        topic,
+        options,
+        &decoders,
+        keep_listening,
+        id.clone(),
+    )
+    .await;
+
+    state.listen_sessions.write().await.remove(&id);
+
+    result
+}
+
+#[tauri::command]
+pub async fn get_messages_page_command<'a>(
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topic: String,
+    cursors: Option<Vec<PartitionCursor>>,
+    page_size: i64,
+    encoding: Option<PayloadEncoding>,
+) -> Result<MessagesPage, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, encoding)?;
+
+    get_previous_page(&common_config, &topic, cursors, page_size, &decoders).await
+}
+
+#[tauri::command]
+pub async fn search_messages_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topic: String,
+    filter: MessageFilter,
+    encoding: Option<PayloadEncoding>,
+    id: String,
+) -> Result<(), String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, encoding)?;
+    let matcher = MessageMatcher::new(filter)?;
+
+    search_messages(window, common_config, topic, &decoders, &matcher, id).await
+}
+
+#[tauri::command]
+pub async fn materialize_topic_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topic: String,
+    options: MaterializeOptions,
+    id: String,
+) -> Result<MaterializedTopic, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, options.encoding)?;
+
+    materialize_topic(window, common_config, topic, options, &decoders, id).await
+}
+
+#[tauri::command]
+pub async fn tombstone_keys_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topics: Vec<String>,
+    options: TombstoneOptions,
+    id: String,
+) -> Result<TombstoneReport, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let producer = match *state.producer.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let mut topic_decoders = vec![];
+    for topic in topics {
+        let decoders = create_topic_decoders(&storage, &topic, codecs, options.encoding)?;
+        topic_decoders.push((topic, decoders));
+    }
+
+    tombstone_keys(
+        window,
+        common_config,
+        &producer,
+        &topic_decoders,
+        options,
+        &storage.audit,
+        id,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn export_messages_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topic: String,
+    options: ExportOptions,
+    id: String,
+) -> Result<SearchProgress, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, options.encoding)?;
+
+    export_messages(window, common_config, topic, options, &decoders, id).await
+}
+
+#[tauri::command]
+pub async fn import_messages_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    topic: String,
+    path: String,
+    options: ImportOptions,
+    id: String,
+) -> Result<ImportProgress, String> {
+    let producer = match *state.producer.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    import_messages(window, &producer, topic, path, options, codecs, id).await
+}
+
+#[tauri::command]
+pub async fn copy_messages_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    topic: String,
+    target_connection: Option<TargetConnection>,
+    options: CopyOptions,
+    id: String,
+) -> Result<CopyProgress, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    // The target producer is only needed for the duration of the copy
+    let producer = match target_connection {
+        Some(target) => target.create_producer()?,
+        None => match *state.producer.read().await {
+            None => return Err("Connection not set".into()),
+            Some(ref x) => x.clone(),
+        },
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &topic, codecs, None)?;
+
+    copy_messages(
+        window,
+        common_config,
+        &producer,
+        topic,
+        options,
+        &decoders,
+        id,
+    )
+    .await
 }
 
 #[tauri::command]
 pub async fn send_message_command<'a>(
+    state: State<'a, KafkaState>,
+    message: OutgoingMessage,
+    producer_options: Option<ProducerOptions>,
+) -> Result<DeliveryReport, String> {
+    // Options of this send only, the connection producer is left untouched
+    let producer = match *state.reporting_producers.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.get(producer_options)?,
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    send_message(&producer, message, codecs).await
+}
+
+#[tauri::command]
+pub async fn send_transaction_command<'a>(
     state: State<'a, KafkaState>,
     topic: String,
-    headers: Option<HashMap<String, Option<&str>>>,
-    key: String,
-    value: Option<String>,
+    messages: Vec<TransactionMessage>,
+    key_format: Option<MessageFormat>,
+    value_format: Option<MessageFormat>,
+    outcome: TransactionOutcome,
+) -> Result<TransactionReport, String> {
+    let mut transactional_producer = state.transactional_producer.lock().await;
+    if transactional_producer.is_none() {
+        let binding = state.producer_config.read().await;
+        let producer_config = match *binding {
+            None => return Err("Connection not set".into()),
+            Some(ref x) => x,
+        };
+        *transactional_producer = Some(TransactionalProducer::new(producer_config).await?);
+    }
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let result = send_transaction(
+        transactional_producer.as_ref().unwrap(),
+        topic,
+        messages,
+        key_format,
+        value_format,
+        outcome,
+        codecs,
+    )
+    .await;
+
+    // The next transaction starts from a new producer, a failed one can be in an unusable state
+    if result.is_err() {
+        *transactional_producer = None;
+    }
+
+    result
+}
+
+#[tauri::command]
+pub async fn request_reply_command<'a>(
+    state: State<'a, KafkaState>,
+    storage: State<'a, StorageState>,
+    request: OutgoingMessage,
+    options: ReplyOptions,
+) -> Result<RoundTrip, String> {
+    let common_config = match *state.common_config.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.clone(),
+    };
+
+    let producer = match *state.reporting_producers.read().await {
+        None => return Err("Connection not set".into()),
+        Some(ref x) => x.get(None)?,
+    };
+
+    let registries = get_registries(&state).await;
+    let codecs = registries.codecs();
+
+    let decoders = create_topic_decoders(&storage, &options.reply_topic, codecs, options.encoding)?;
+
+    request_reply(
+        &common_config,
+        &producer,
+        request,
+        options,
+        &decoders,
+        codecs,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn start_autosend_command<'a>(
+    window: Window,
+    state: State<'a, KafkaState>,
+    autosend: Autosend,
+    id: String,
 ) -> Result<(), String> {
-    let binding = state.producer.read().await;
-    let producer = match *binding {
+    let producer = match *state.reporting_producers.read().await {
         None => return Err("Connection not set".into()),
-        Some(ref x) => x,
+        Some(ref x) => x.get(None)?,
     };
 
-    send_message(producer, topic, headers, key, value).await
+    start_autosend(window, producer, autosend, state.autosends.clone(), id).await
+}
+
+#[tauri::command]
+pub async fn stop_autosend_command<'a>(
+    state: State<'a, KafkaState>,
+    id: String,
+) -> Result<(), String> {
+    match state.autosends.read().await.get(&id) {
+        None => Err(format!("Autosend {} not found", id)),
+        Some(job) => {
+            job.stop();
+            Ok(())
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_autosends_command<'a>(
+    state: State<'a, KafkaState>,
+) -> Result<Vec<AutosendStatus>, String> {
+    let autosends = state.autosends.read().await;
+    Ok(autosends.values().map(|job| job.status()).collect())
+}
+
+/// Registries of the connection, cloned out of the state so that no lock is held while using them
+struct Registries {
+    schema_registry: Option<Arc<SchemaRegistryClient>>,
+    protobuf: Option<Arc<ProtobufRegistry>>,
+}
+
+impl Registries {
+    fn codecs(&self) -> MessageCodecs<'_> {
+        MessageCodecs {
+            schema_registry: self.schema_registry.as_deref(),
+            protobuf: self.protobuf.as_deref(),
+        }
+    }
+}
+
+async fn get_registries(state: &KafkaState) -> Registries {
+    Registries {
+        schema_registry: state.schema_registry.read().await.clone(),
+        protobuf: state.protobuf.read().await.clone(),
+    }
+}
+
+/// Decoders of the topic from the DECODERS setting
+fn create_topic_decoders<'a>(
+    storage: &StorageState,
+    topic: &str,
+    codecs: MessageCodecs<'a>,
+    encoding: Option<PayloadEncoding>,
+) -> Result<TopicDecoders<'a>, String> {
+    let rules = get_decoder_rules(&storage.settings)?;
+    let encoding = encoding.unwrap_or(PayloadEncoding::Utf8);
+    TopicDecoders::new(topic, &rules, codecs, encoding)
 }
 
 fn get_store<'a>(
@@ -230,6 +647,7 @@ fn get_store<'a>(
     let store = match store_name {
         "settings" => Ok(&state.settings),
         "messages" => Ok(&state.messages),
+        "audit" => Ok(&state.audit),
         &_ => Err(format!("Unexpected error, unknown store {}", store_name)),
     }?;
 
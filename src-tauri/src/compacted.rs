@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use rdkafka::ClientConfig;
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+
+use crate::decoders::TopicDecoders;
+use crate::encoding::PayloadEncoding;
+use crate::export::{write_messages_file, ExportFormat};
+use crate::filters::{MessageFilter, MessageMatcher};
+use crate::messages::KafkaMessageResponse;
+use crate::search::{create_topic_readers, scan_topic, SearchProgress};
+
+/// File the materialized records are written to, after the filter is applied
+#[derive(Deserialize, Debug, Clone)]
+pub struct MaterializedExport {
+    pub path: String,
+    pub format: ExportFormat,
+}
+
+/// What to keep of the materialized topic
+#[derive(Deserialize, Debug, Clone)]
+pub struct MaterializeOptions {
+    /// Applied to the latest record of every key
+    pub filter: Option<MessageFilter>,
+    /// Used for the fields no decoder applies to
+    pub encoding: Option<PayloadEncoding>,
+    pub export: Option<MaterializedExport>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MaterializedTopic {
+    /// Latest record of every live key matching the filter, sorted by key
+    pub records: Vec<KafkaMessageResponse>,
+    /// Live keys before the filter is applied
+    pub keys: u64,
+    /// Keys whose latest record is a tombstone
+    pub tombstones: u64,
+    /// Keyless records, not part of the table
+    pub keyless: u64,
+    /// The scan was cancelled, older records might still be shown for some keys
+    pub partial: bool,
+    pub progress: SearchProgress,
+}
+
+/// Read the whole topic keeping only the latest record of every key, like a compacted topic
+/// or a KTable. Progress is emitted as `onMaterializeProgress-{id}` and the scan is stopped by
+/// `offMaterialize-{id}`, the partial table is then returned without being exported
+pub async fn materialize_topic(
+    window: Window,
+    common_config: ClientConfig,
+    topic: String,
+    options: MaterializeOptions,
+    decoders: &TopicDecoders<'_>,
+    id: String,
+) -> Result<MaterializedTopic, String> {
+    let matcher = MessageMatcher::from_filter(options.filter)?;
+    let readers = create_topic_readers(&common_config, &topic, None)?;
+    let latest = LatestRecords::new();
+    let keyless = Mutex::new(0);
+
+    // The filter applies to the latest records only, not to the ones they replaced
+    let progress = scan_topic(
+        &window,
+        &readers,
+        decoders,
+        None,
+        "Materialize",
+        &id,
+        |_, message| {
+            match message.key {
+                Some(ref key) => latest.insert(key.clone(), message),
+                None => *keyless.lock().unwrap() += 1,
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    let (tombstones, mut live): (Vec<_>, Vec<_>) = latest
+        .into_records()
+        .into_values()
+        .partition(|message| message.value.is_none());
+    let keys = live.len() as u64;
+
+    if let Some(ref matcher) = matcher {
+        live.retain(|message| message.matches(matcher));
+    }
+    live.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let partial = progress.cancelled;
+    if let Some(export) = options.export {
+        if !partial {
+            write_messages_file(&export.path, export.format, &live)?;
+        }
+    }
+
+    Ok(MaterializedTopic {
+        records: live,
+        keys,
+        tombstones: tombstones.len() as u64,
+        keyless: keyless.into_inner().unwrap(),
+        partial,
+        progress,
+    })
+}
+
+/// Latest record of every key of a topic scan
+pub(crate) struct LatestRecords<K> {
+    records: Mutex<HashMap<K, KafkaMessageResponse>>,
+}
+
+impl<K: Eq + Hash> LatestRecords<K> {
+    pub(crate) fn new() -> Self {
+        LatestRecords {
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn insert(&self, key: K, message: KafkaMessageResponse) {
+        let mut records = self.records.lock().unwrap();
+        let is_newer = records
+            .get(&key)
+            .map_or(true, |current| is_newer(current, &message));
+        if is_newer {
+            records.insert(key, message);
+        }
+    }
+
+    pub(crate) fn into_records(self) -> HashMap<K, KafkaMessageResponse> {
+        self.records.into_inner().unwrap()
+    }
+}
+
+/// Partitions are read in offset order, so a later record of the same partition always wins.
+/// Keys are expected in a single partition, the newest record wins otherwise
+fn is_newer(current: &KafkaMessageResponse, message: &KafkaMessageResponse) -> bool {
+    message.partition == current.partition
+        || (message.timestamp, message.offset) >= (current.timestamp, current.offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(partition: i32, offset: i64, timestamp: i64, value: &str) -> KafkaMessageResponse {
+        KafkaMessageResponse {
+            headers: None,
+            value: Some(value.to_owned()),
+            key: Some("key".to_owned()),
+            offset,
+            partition,
+            timestamp,
+            headers_encoding: None,
+            value_encoding: None,
+            key_encoding: None,
+        }
+    }
+
+    fn latest_value(messages: Vec<KafkaMessageResponse>) -> Option<String> {
+        let latest = LatestRecords::new();
+        for message in messages {
+            latest.insert("key", message);
+        }
+        latest.into_records().remove("key").unwrap().value
+    }
+
+    #[test]
+    fn keeps_the_last_record_of_a_partition_whatever_its_timestamp() {
+        let value = latest_value(vec![
+            message(0, 1, 500, "first"),
+            message(0, 2, 100, "last"),
+        ]);
+
+        assert_eq!(value.as_deref(), Some("last"));
+    }
+
+    #[test]
+    fn keeps_the_newest_record_across_partitions() {
+        let value = latest_value(vec![
+            message(1, 7, 500, "newest"),
+            message(0, 2, 100, "older"),
+        ]);
+
+        assert_eq!(value.as_deref(), Some("newest"));
+    }
+}
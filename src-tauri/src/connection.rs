@@ -2,7 +2,10 @@ use rdkafka::{
     admin::AdminClient, client::DefaultClientContext, config::RDKafkaLogLevel,
     consumer::StreamConsumer, producer::FutureProducer, ClientConfig,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::protobuf::{ProtobufDescriptorConfig, ProtobufRegistry};
+use crate::schema_registry::{SchemaRegistryClient, SchemaRegistryConfig};
 
 #[derive(Deserialize, Debug)]
 pub struct SaslConfig {
@@ -11,34 +14,100 @@ pub struct SaslConfig {
     password: String,
 }
 
+/// How the producer picks the partition of keyed messages, keyless ones go to a random partition
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Partitioner {
+    /// librdkafka default, CRC32 hash of the key
+    ConsistentRandom,
+    /// Same partitions as the Java client and KafkaJS
+    Murmur2Random,
+    /// Same partitions as Sarama
+    Fnv1a,
+}
+
+impl Partitioner {
+    fn as_config(&self) -> &'static str {
+        match self {
+            Partitioner::ConsistentRandom => "consistent_random",
+            Partitioner::Murmur2Random => "murmur2_random",
+            Partitioner::Fnv1a => "fnv1a_random",
+        }
+    }
+}
+
+/// Acknowledgements the producer waits for before a message counts as delivered
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Acks {
+    None,
+    Leader,
+    All,
+}
+
+impl Acks {
+    fn as_config(&self) -> &'static str {
+        match self {
+            Acks::None => "0",
+            Acks::Leader => "1",
+            Acks::All => "all",
+        }
+    }
+}
+
+/// Delivery guarantees of the producer, librdkafka defaults when not set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ProducerOptions {
+    /// No duplicates nor reordering on retries, requires acks `All`
+    pub idempotence: Option<bool>,
+    pub acks: Option<Acks>,
+}
+
+impl ProducerOptions {
+    pub fn apply(&self, config: &mut ClientConfig) {
+        if let Some(idempotence) = self.idempotence {
+            config.set("enable.idempotence", idempotence.to_string());
+        }
+        if let Some(acks) = self.acks {
+            config.set("acks", acks.as_config());
+        }
+    }
+}
+
+/// Connection as saved in the settings, registries are optional
+#[derive(Deserialize, Debug)]
+pub struct ConnectionConfig {
+    pub brokers: Vec<String>,
+    pub group_id: String,
+    pub sasl: Option<SaslConfig>,
+    pub partitioner: Option<Partitioner>,
+    pub producer: Option<ProducerOptions>,
+    pub schema_registry: Option<SchemaRegistryConfig>,
+    pub protobuf: Option<Vec<ProtobufDescriptorConfig>>,
+}
+
 pub struct Connections {
     pub common_config: ClientConfig,
+    /// Used to create dedicated producers, e.g. transactional ones
+    pub producer_config: ClientConfig,
     pub admin: AdminClient<DefaultClientContext>,
     pub consumer: StreamConsumer,
     pub producer: FutureProducer,
+    pub schema_registry: Option<SchemaRegistryClient>,
+    pub protobuf: Option<ProtobufRegistry>,
 }
 
 #[tauri::command]
-pub async fn create_connections(
-    brokers: Vec<String>,
-    group_id: String,
-    sasl: Option<SaslConfig>,
-) -> Result<Connections, String> {
-    let mut common_config = ClientConfig::new();
-    common_config.set_log_level(RDKafkaLogLevel::Warning);
-    common_config.set("bootstrap.servers", &brokers.join(","));
-    common_config.set("group.id", group_id);
+pub async fn create_connections(config: ConnectionConfig) -> Result<Connections, String> {
+    let ConnectionConfig {
+        brokers,
+        group_id,
+        sasl,
+        partitioner,
+        producer: producer_options,
+        schema_registry,
+        protobuf,
+    } = config;
 
-    if let Some(sasl) = sasl {
-        if sasl.mechanism != "PLAIN" {
-            common_config.set("security.protocol", "SASL_SSL");
-        }
-
-        common_config
-            .set("sasl.mechanism", sasl.mechanism)
-            .set("sasl.username", sasl.username)
-            .set("sasl.password", sasl.password);
-    }
+    let common_config = create_common_config(&brokers, group_id, sasl);
 
     let admin: AdminClient<_> = common_config
         .clone()
@@ -52,16 +121,70 @@ pub async fn create_connections(
         .create()
         .map_err(|err| format!("Error creating consumer connection: {}", err.to_string()))?;
 
-    let producer: FutureProducer = common_config
-        .clone()
-        .set("message.timeout.ms", "5000")
+    let producer_config = create_producer_config(&common_config, partitioner, producer_options);
+    let producer: FutureProducer = producer_config
         .create()
         .map_err(|err| format!("Error creating producer connection: {}", err.to_string()))?;
 
+    let schema_registry = match schema_registry {
+        Some(config) => Some(SchemaRegistryClient::new(config)?),
+        None => None,
+    };
+
+    let protobuf = match protobuf {
+        Some(configs) => Some(ProtobufRegistry::new(configs)?),
+        None => None,
+    };
+
     Ok(Connections {
         common_config,
+        producer_config,
         admin,
         consumer,
         producer,
+        schema_registry,
+        protobuf,
     })
 }
+
+/// Settings shared by every client of the connection
+pub(crate) fn create_common_config(
+    brokers: &[String],
+    group_id: String,
+    sasl: Option<SaslConfig>,
+) -> ClientConfig {
+    let mut common_config = ClientConfig::new();
+    common_config.set_log_level(RDKafkaLogLevel::Warning);
+    common_config.set("bootstrap.servers", brokers.join(","));
+    common_config.set("group.id", group_id);
+
+    if let Some(sasl) = sasl {
+        if sasl.mechanism != "PLAIN" {
+            common_config.set("security.protocol", "SASL_SSL");
+        }
+
+        common_config
+            .set("sasl.mechanism", sasl.mechanism)
+            .set("sasl.username", sasl.username)
+            .set("sasl.password", sasl.password);
+    }
+
+    common_config
+}
+
+pub(crate) fn create_producer_config(
+    common_config: &ClientConfig,
+    partitioner: Option<Partitioner>,
+    producer_options: Option<ProducerOptions>,
+) -> ClientConfig {
+    let mut producer_config = common_config.clone();
+    producer_config.set("message.timeout.ms", "5000");
+    if let Some(partitioner) = partitioner {
+        producer_config.set("partitioner", partitioner.as_config());
+    }
+    if let Some(producer_options) = producer_options {
+        producer_options.apply(&mut producer_config);
+    }
+
+    producer_config
+}
@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{BorrowedMessage, OwnedHeaders};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord};
+use rdkafka::{ClientConfig, Message};
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time::Duration;
+
+use crate::connection::{
+    create_common_config, create_producer_config, Partitioner, ProducerOptions, SaslConfig,
+};
+use crate::decoders::TopicDecoders;
+use crate::filters::{MessageFilter, MessageMatcher};
+use crate::messages::{KafkaMessageResponse, PartitionRange};
+use crate::search::{create_topic_readers, scan_topic_with, ScanHandler};
+
+/// Another saved connection to copy the messages to, same fields as when setting the connection.
+/// Its registries are left out, the records are copied as raw bytes without any codec
+#[derive(Deserialize, Debug)]
+pub struct TargetConnection {
+    pub brokers: Vec<String>,
+    pub group_id: String,
+    pub sasl: Option<SaslConfig>,
+    pub partitioner: Option<Partitioner>,
+    pub producer: Option<ProducerOptions>,
+}
+
+impl TargetConnection {
+    /// Only a producer is needed to write to the target
+    pub fn create_producer(self) -> Result<FutureProducer, String> {
+        let common_config = create_common_config(&self.brokers, self.group_id, self.sasl);
+        create_producer_config(&common_config, self.partitioner, self.producer)
+            .create()
+            .map_err(|err| format!("Error creating producer connection: {}", err.to_string()))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CopyOptions {
+    pub target_topic: String,
+    /// Whole topic when not set
+    pub ranges: Option<Vec<PartitionRange>>,
+    pub filter: Option<MessageFilter>,
+    pub keep_partition: bool,
+    /// Restamped with the time of the copy when false
+    pub keep_timestamps: bool,
+    /// Sent without headers when false
+    pub keep_headers: bool,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct CopyProgress {
+    pub scanned: i64,
+    pub matched: u64,
+    pub delivered: u64,
+    pub failed: u64,
+}
+
+/// Re-produce the messages of the source topic, whole topic unless narrowed down by ranges or
+/// filter, to the target topic with the given producer. Keys and values are copied byte for
+/// byte, the decoders only serve the filter, so schema ids in registry framed payloads still
+/// refer to the source registry. Scan progress is emitted as `onCopyProgress-{id}` and the copy
+/// is stopped by `offCopy-{id}`
+pub async fn copy_messages(
+    window: Window,
+    common_config: ClientConfig,
+    producer: &FutureProducer,
+    topic: String,
+    options: CopyOptions,
+    decoders: &TopicDecoders<'_>,
+    id: String,
+) -> Result<CopyProgress, String> {
+    let matcher = MessageMatcher::from_filter(options.filter.clone())?;
+    let readers = create_topic_readers(&common_config, &topic, options.ranges.as_deref())?;
+
+    let delivered = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicU64::new(0));
+
+    // Delivery reports are counted in the order the records were sent, until the copy is done
+    let (deliveries, mut pending) = mpsc::unbounded_channel::<DeliveryFuture>();
+    let counter = {
+        let delivered = delivered.clone();
+        let failed = failed.clone();
+        tokio::spawn(async move {
+            while let Some(delivery) = pending.recv().await {
+                match delivery.await {
+                    Ok(Ok(_)) => delivered.fetch_add(1, Ordering::Relaxed),
+                    _ => failed.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+        })
+    };
+
+    let handler = CopyHandler {
+        producer,
+        options: &options,
+        deliveries,
+        delivered: &delivered,
+        failed: &failed,
+    };
+    let scan_progress = scan_topic_with(
+        &window,
+        &readers,
+        decoders,
+        matcher.as_ref(),
+        "Copy",
+        &id,
+        &handler,
+    )
+    .await;
+
+    // Closes the channel so that the counter stops once every delivery is counted
+    drop(handler);
+    counter
+        .await
+        .map_err(|err| format!("Error while copying the messages: {}", err.to_string()))?;
+    let scan_progress = scan_progress?;
+
+    Ok(CopyProgress {
+        scanned: scan_progress.scanned,
+        matched: scan_progress.matched,
+        delivered: delivered.load(Ordering::Relaxed),
+        failed: failed.load(Ordering::Relaxed),
+    })
+}
+
+struct CopyHandler<'a> {
+    producer: &'a FutureProducer,
+    options: &'a CopyOptions,
+    deliveries: UnboundedSender<DeliveryFuture>,
+    delivered: &'a AtomicU64,
+    failed: &'a AtomicU64,
+}
+
+#[async_trait]
+impl<'a> ScanHandler for CopyHandler<'a> {
+    async fn on_message(
+        &self,
+        message: &BorrowedMessage<'_>,
+        _: KafkaMessageResponse,
+    ) -> Result<(), String> {
+        let headers = match message.headers() {
+            Some(headers) if self.options.keep_headers => headers.detach(),
+            _ => OwnedHeaders::new(),
+        };
+
+        let mut record = FutureRecord::to(&self.options.target_topic).headers(headers);
+        if let Some(key) = message.key() {
+            record = record.key(key);
+        }
+        if let Some(payload) = message.payload() {
+            record = record.payload(payload);
+        }
+        if self.options.keep_partition {
+            record = record.partition(message.partition());
+        }
+        if self.options.keep_timestamps {
+            if let Some(timestamp) = message.timestamp().to_millis() {
+                record = record.timestamp(timestamp);
+            }
+        }
+
+        loop {
+            match self.producer.send_result(record) {
+                Ok(delivery) => {
+                    // The counter only stops once the handler is dropped
+                    self.deliveries.send(delivery).unwrap();
+                    return Ok(());
+                }
+                // Give the producer some time to empty its queue
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    record = returned;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err((err, _)) => {
+                    return Err(format!(
+                        "Error while copying the message to topic {}: {}",
+                        self.options.target_topic,
+                        err.to_string()
+                    ))
+                }
+            }
+        }
+    }
+
+    fn deliveries(&self) -> Option<(u64, u64)> {
+        Some((
+            self.delivered.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+        ))
+    }
+}
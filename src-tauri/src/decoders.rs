@@ -0,0 +1,353 @@
+use async_trait::async_trait;
+use jfs::Store;
+use prost_reflect::MessageDescriptor;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::encoding::{encode_payload, AppliedEncoding, PayloadEncoding};
+use crate::messages::MessageCodecs;
+use crate::protobuf::{decode_protobuf, MessagePart};
+use crate::schema_registry::{decode_avro, SchemaRegistryClient};
+use crate::storage::get_from_store;
+
+/// Decoder to use for a part of the message, as saved in the DECODERS setting
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum DecoderConfig {
+    /// Protobuf message bound to the topic, then schema registry, then text
+    Auto,
+    String,
+    Json,
+    /// Confluent Avro through the connection schema registry
+    Avro,
+    /// The message bound to the topic in the connection descriptor sets when not given
+    Protobuf {
+        message: Option<String>,
+    },
+    /// Big endian signed integer of 1, 2, 4 or 8 bytes
+    Integer,
+    Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecoderRule {
+    /// Regex matched against the whole topic name
+    pub topic_pattern: String,
+    pub key: Option<DecoderConfig>,
+    pub value: Option<DecoderConfig>,
+    pub headers: Option<DecoderConfig>,
+}
+
+pub fn get_decoder_rules(settings: &Store) -> Result<Vec<DecoderRule>, String> {
+    match get_from_store(settings, "DECODERS")? {
+        Some(rules) => serde_json::from_value(rules)
+            .map_err(|err| format!("Invalid DECODERS setting: {}", err.to_string())),
+        None => Ok(vec![]),
+    }
+}
+
+#[async_trait]
+pub trait MessageDecoder: Send + Sync {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String>;
+}
+
+/// Decoders for key, value and headers of a single topic, chosen when starting to listen
+pub struct TopicDecoders<'a> {
+    key: Box<dyn MessageDecoder + 'a>,
+    value: Box<dyn MessageDecoder + 'a>,
+    headers: Box<dyn MessageDecoder + 'a>,
+    encoding: PayloadEncoding,
+}
+
+impl<'a> TopicDecoders<'a> {
+    /// The first rule matching the topic wins, parts without a decoder fall back to Auto
+    pub fn new(
+        topic: &str,
+        rules: &[DecoderRule],
+        codecs: MessageCodecs<'a>,
+        encoding: PayloadEncoding,
+    ) -> Result<TopicDecoders<'a>, String> {
+        let mut rule = None;
+        for candidate in rules {
+            let pattern =
+                Regex::new(&format!("^(?:{})$", candidate.topic_pattern)).map_err(|err| {
+                    format!(
+                        "Invalid topic pattern {} in decoders: {}",
+                        candidate.topic_pattern,
+                        err.to_string()
+                    )
+                })?;
+            if pattern.is_match(topic) {
+                rule = Some(candidate);
+                break;
+            }
+        }
+
+        let key_config = rule.and_then(|rule| rule.key.clone());
+        let value_config = rule.and_then(|rule| rule.value.clone());
+        let headers_config = rule
+            .and_then(|rule| rule.headers.clone())
+            .unwrap_or(DecoderConfig::String);
+
+        Ok(TopicDecoders {
+            key: create_decoder(key_config, topic, MessagePart::Key, codecs, encoding)?,
+            value: create_decoder(value_config, topic, MessagePart::Value, codecs, encoding)?,
+            headers: create_decoder(
+                Some(headers_config),
+                topic,
+                MessagePart::Headers,
+                codecs,
+                encoding,
+            )?,
+            encoding,
+        })
+    }
+
+    pub async fn decode_key(&self, bytes: &[u8]) -> (String, AppliedEncoding) {
+        self.decode_with(self.key.as_ref(), bytes).await
+    }
+
+    pub async fn decode_value(&self, bytes: &[u8]) -> (String, AppliedEncoding) {
+        self.decode_with(self.value.as_ref(), bytes).await
+    }
+
+    pub async fn decode_header(&self, bytes: &[u8]) -> (String, AppliedEncoding) {
+        self.decode_with(self.headers.as_ref(), bytes).await
+    }
+
+    async fn decode_with(
+        &self,
+        decoder: &(dyn MessageDecoder + 'a),
+        bytes: &[u8],
+    ) -> (String, AppliedEncoding) {
+        match decoder.decode(bytes).await {
+            Ok(decoded) => decoded,
+            // Do not stop the whole stream for a single undecodable message
+            Err(err) => {
+                log::warn!("{}", err);
+                encode_payload(bytes, self.encoding)
+            }
+        }
+    }
+}
+
+fn create_decoder<'a>(
+    config: Option<DecoderConfig>,
+    topic: &str,
+    part: MessagePart,
+    codecs: MessageCodecs<'a>,
+    encoding: PayloadEncoding,
+) -> Result<Box<dyn MessageDecoder + 'a>, String> {
+    let decoder: Box<dyn MessageDecoder + 'a> = match config.unwrap_or(DecoderConfig::Auto) {
+        DecoderConfig::Auto => Box::new(AutoDecoder {
+            protobuf: codecs
+                .protobuf
+                .and_then(|protobuf| protobuf.get_topic_message(topic, part))
+                .cloned(),
+            schema_registry: codecs.schema_registry,
+            encoding,
+        }),
+        DecoderConfig::String => Box::new(StringDecoder { encoding }),
+        DecoderConfig::Json => Box::new(JsonDecoder),
+        DecoderConfig::Avro => Box::new(AvroDecoder {
+            schema_registry: codecs
+                .schema_registry
+                .ok_or("Avro decoder needs a schema registry configured for the connection")?,
+        }),
+        DecoderConfig::Protobuf { message } => {
+            let protobuf = codecs
+                .protobuf
+                .ok_or("Protobuf decoder needs descriptor sets configured for the connection")?;
+            let descriptor = match message {
+                Some(ref message) => protobuf.get_message(message),
+                None => protobuf.get_topic_message(topic, part).cloned(),
+            };
+            Box::new(ProtobufDecoder {
+                descriptor: descriptor.ok_or(format!(
+                    "No protobuf message {} found for topic {}",
+                    message.unwrap_or_default(),
+                    topic
+                ))?,
+            })
+        }
+        DecoderConfig::Integer => Box::new(IntegerDecoder),
+        DecoderConfig::Uuid => Box::new(UuidDecoder),
+    };
+
+    Ok(decoder)
+}
+
+struct AutoDecoder<'a> {
+    protobuf: Option<MessageDescriptor>,
+    schema_registry: Option<&'a SchemaRegistryClient>,
+    encoding: PayloadEncoding,
+}
+
+#[async_trait]
+impl<'a> MessageDecoder for AutoDecoder<'a> {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        if let Some(ref descriptor) = self.protobuf {
+            let json = decode_protobuf(descriptor, bytes)?;
+            return Ok((json.to_string(), AppliedEncoding::Protobuf));
+        }
+
+        if let Some(schema_registry) = self.schema_registry {
+            if let Some(json) = decode_avro(schema_registry, bytes).await? {
+                return Ok((json.to_string(), AppliedEncoding::Avro));
+            }
+        }
+
+        Ok(encode_payload(bytes, self.encoding))
+    }
+}
+
+struct StringDecoder {
+    encoding: PayloadEncoding,
+}
+
+#[async_trait]
+impl MessageDecoder for StringDecoder {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        Ok(encode_payload(bytes, self.encoding))
+    }
+}
+
+struct JsonDecoder;
+
+#[async_trait]
+impl MessageDecoder for JsonDecoder {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        // Only validated, the original text is kept so that it can be written back as is
+        let text = std::str::from_utf8(bytes)
+            .map_err(|err| format!("Invalid JSON payload: {}", err.to_string()))?;
+        serde_json::from_str::<serde::de::IgnoredAny>(text)
+            .map_err(|err| format!("Invalid JSON payload: {}", err.to_string()))?;
+        Ok((text.to_owned(), AppliedEncoding::Json))
+    }
+}
+
+struct AvroDecoder<'a> {
+    schema_registry: &'a SchemaRegistryClient,
+}
+
+#[async_trait]
+impl<'a> MessageDecoder for AvroDecoder<'a> {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        match decode_avro(self.schema_registry, bytes).await? {
+            Some(json) => Ok((json.to_string(), AppliedEncoding::Avro)),
+            None => Err("Avro payload is not in the Confluent wire format".to_owned()),
+        }
+    }
+}
+
+struct ProtobufDecoder {
+    descriptor: MessageDescriptor,
+}
+
+#[async_trait]
+impl MessageDecoder for ProtobufDecoder {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        let json = decode_protobuf(&self.descriptor, bytes)?;
+        Ok((json.to_string(), AppliedEncoding::Protobuf))
+    }
+}
+
+struct IntegerDecoder;
+
+#[async_trait]
+impl MessageDecoder for IntegerDecoder {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        let integer = match bytes.len() {
+            1 => (
+                i8::from_be_bytes([bytes[0]]).to_string(),
+                AppliedEncoding::Int8,
+            ),
+            2 => (
+                i16::from_be_bytes([bytes[0], bytes[1]]).to_string(),
+                AppliedEncoding::Int16,
+            ),
+            4 => (
+                i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).to_string(),
+                AppliedEncoding::Int32,
+            ),
+            8 => {
+                let mut buffer = [0u8; 8];
+                buffer.copy_from_slice(bytes);
+                (
+                    i64::from_be_bytes(buffer).to_string(),
+                    AppliedEncoding::Int64,
+                )
+            }
+            length => return Err(format!("Invalid integer payload of {} bytes", length)),
+        };
+
+        Ok(integer)
+    }
+}
+
+struct UuidDecoder;
+
+#[async_trait]
+impl MessageDecoder for UuidDecoder {
+    async fn decode(&self, bytes: &[u8]) -> Result<(String, AppliedEncoding), String> {
+        // Both the 16 bytes binary and the textual representations are accepted, the textual
+        // one is kept as is so that it is written back as text
+        if let Ok(uuid) = Uuid::from_slice(bytes) {
+            return Ok((uuid.to_string(), AppliedEncoding::Uuid));
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(text) if Uuid::parse_str(text).is_ok() => {
+                Ok((text.to_owned(), AppliedEncoding::Utf8))
+            }
+            _ => Err("Invalid UUID payload".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::decode_payload;
+
+    async fn uuid_round_trip(bytes: &[u8]) -> (AppliedEncoding, Vec<u8>) {
+        let (text, encoding) = UuidDecoder.decode(bytes).await.unwrap();
+        (encoding, decode_payload(&text, encoding).unwrap())
+    }
+
+    #[tokio::test]
+    async fn restores_binary_uuids() {
+        let uuid = Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+
+        let (encoding, bytes) = uuid_round_trip(uuid.as_bytes()).await;
+
+        assert_eq!(encoding, AppliedEncoding::Uuid);
+        assert_eq!(bytes, uuid.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn restores_textual_uuids_as_text() {
+        let text = "67E55044-10B1-426F-9247-BB680E5FE0C8";
+
+        let (encoding, bytes) = uuid_round_trip(text.as_bytes()).await;
+
+        assert_eq!(encoding, AppliedEncoding::Utf8);
+        assert_eq!(bytes, text.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_original_json_text() {
+        let text = "{ \"b\": 1,\n  \"a\": [1, 2] }";
+
+        let (json, encoding) = JsonDecoder.decode(text.as_bytes()).await.unwrap();
+
+        assert_eq!(encoding, AppliedEncoding::Json);
+        assert_eq!(decode_payload(&json, encoding).unwrap(), text.as_bytes());
+        assert!(JsonDecoder.decode(b"{\"a\":").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_uuids() {
+        assert!(UuidDecoder.decode(b"not a uuid").await.is_err());
+    }
+}
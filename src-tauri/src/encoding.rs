@@ -0,0 +1,192 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// How a message field (key, value or header) is turned into a string for the frontend when
+/// no decoder applies to it
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PayloadEncoding {
+    /// Plain text, falls back to Base64 when the bytes are not valid UTF-8
+    Utf8,
+    Base64,
+    Hex,
+    /// UTF-8 where invalid sequences are replaced with U+FFFD
+    Lossy,
+}
+
+/// How a message field was actually turned into a string, sent along with the field
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum AppliedEncoding {
+    Utf8,
+    Base64,
+    Hex,
+    Lossy,
+    /// Confluent Avro payload decoded to JSON through the schema registry
+    Avro,
+    /// Protobuf payload decoded to JSON with a connection descriptor set
+    Protobuf,
+    /// Valid JSON text, kept as it was produced
+    Json,
+    /// Big endian signed integers, by width so that they are written back on as many bytes
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    /// 16 bytes binary UUID, textual UUIDs are kept as Utf8
+    Uuid,
+}
+
+pub fn encode_payload(bytes: &[u8], mode: PayloadEncoding) -> (String, AppliedEncoding) {
+    match mode {
+        PayloadEncoding::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_owned(), AppliedEncoding::Utf8),
+            Err(_) => encode_payload(bytes, PayloadEncoding::Base64),
+        },
+        PayloadEncoding::Base64 => (STANDARD.encode(bytes), AppliedEncoding::Base64),
+        PayloadEncoding::Hex => (hex::encode(bytes), AppliedEncoding::Hex),
+        PayloadEncoding::Lossy => (
+            String::from_utf8_lossy(bytes).into_owned(),
+            AppliedEncoding::Lossy,
+        ),
+    }
+}
+
+/// Turn a field encoded by `encode_payload` or by the topic decoders back into the same bytes.
+/// Payloads decoded to JSON through a schema (Avro, Protobuf) and lossy text with replaced
+/// sequences can't be restored and are rejected.
+pub fn decode_payload(text: &str, encoding: AppliedEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        AppliedEncoding::Lossy if text.contains(char::REPLACEMENT_CHARACTER) => {
+            Err("Lossy payload has replaced bytes that can't be restored".to_owned())
+        }
+        AppliedEncoding::Utf8 | AppliedEncoding::Lossy | AppliedEncoding::Json => {
+            Ok(text.as_bytes().to_vec())
+        }
+        AppliedEncoding::Base64 => STANDARD
+            .decode(text)
+            .map_err(|err| format!("Invalid base64 payload: {}", err.to_string())),
+        AppliedEncoding::Hex => {
+            hex::decode(text).map_err(|err| format!("Invalid hex payload: {}", err.to_string()))
+        }
+        AppliedEncoding::Int8 => Ok(parse_integer::<i8>(text)?.to_be_bytes().to_vec()),
+        AppliedEncoding::Int16 => Ok(parse_integer::<i16>(text)?.to_be_bytes().to_vec()),
+        AppliedEncoding::Int32 => Ok(parse_integer::<i32>(text)?.to_be_bytes().to_vec()),
+        AppliedEncoding::Int64 => Ok(parse_integer::<i64>(text)?.to_be_bytes().to_vec()),
+        AppliedEncoding::Uuid => uuid::Uuid::parse_str(text)
+            .map(|uuid| uuid.as_bytes().to_vec())
+            .map_err(|err| format!("Invalid UUID payload: {}", err.to_string())),
+        AppliedEncoding::Avro | AppliedEncoding::Protobuf => Err(format!(
+            "{:?} payload was decoded to JSON and can't be restored as is, \
+            choose a format to encode it with",
+            encoding
+        )),
+    }
+}
+
+fn parse_integer<T: FromStr<Err = ParseIntError>>(text: &str) -> Result<T, String> {
+    text.parse::<T>()
+        .map_err(|err| format!("Invalid integer payload: {}", err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_valid_utf8_as_text() {
+        assert_eq!(
+            encode_payload("héllo".as_bytes(), PayloadEncoding::Utf8),
+            ("héllo".to_owned(), AppliedEncoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_base64_for_binary_utf8() {
+        assert_eq!(
+            encode_payload(&[0xff, 0x00, 0x41], PayloadEncoding::Utf8),
+            ("/wBB".to_owned(), AppliedEncoding::Base64)
+        );
+    }
+
+    #[test]
+    fn encodes_with_the_requested_mode() {
+        let bytes = [0x68, 0xff, 0x69];
+        assert_eq!(
+            encode_payload(&bytes, PayloadEncoding::Base64),
+            ("aP9p".to_owned(), AppliedEncoding::Base64)
+        );
+        assert_eq!(
+            encode_payload(&bytes, PayloadEncoding::Hex),
+            ("68ff69".to_owned(), AppliedEncoding::Hex)
+        );
+        assert_eq!(
+            encode_payload(&bytes, PayloadEncoding::Lossy),
+            ("h\u{fffd}i".to_owned(), AppliedEncoding::Lossy)
+        );
+    }
+
+    #[test]
+    fn rejects_requests_for_applied_only_encodings() {
+        assert!(serde_json::from_str::<PayloadEncoding>(r#""Hex""#).is_ok());
+        assert!(serde_json::from_str::<PayloadEncoding>(r#""Avro""#).is_err());
+        assert!(serde_json::from_str::<AppliedEncoding>(r#""Avro""#).is_ok());
+    }
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        for mode in [
+            PayloadEncoding::Utf8,
+            PayloadEncoding::Base64,
+            PayloadEncoding::Hex,
+        ] {
+            let (text, encoding) = encode_payload(&bytes, mode);
+            assert_eq!(decode_payload(&text, encoding).unwrap(), bytes);
+        }
+
+        let (text, encoding) = encode_payload(b"text", PayloadEncoding::Utf8);
+        assert_eq!(decode_payload(&text, encoding).unwrap(), b"text");
+    }
+
+    #[test]
+    fn reports_invalid_payloads() {
+        assert!(decode_payload("not base64!", AppliedEncoding::Base64).is_err());
+        assert!(decode_payload("zz", AppliedEncoding::Hex).is_err());
+        assert!(decode_payload("12a", AppliedEncoding::Int64).is_err());
+        assert!(decode_payload("not-a-uuid", AppliedEncoding::Uuid).is_err());
+    }
+
+    #[test]
+    fn writes_integers_back_on_their_width() {
+        assert_eq!(
+            decode_payload("-2", AppliedEncoding::Int8).unwrap(),
+            vec![0xfe]
+        );
+        assert_eq!(
+            decode_payload("258", AppliedEncoding::Int16).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            decode_payload("-1", AppliedEncoding::Int32).unwrap(),
+            vec![0xff; 4]
+        );
+        assert_eq!(
+            decode_payload("1", AppliedEncoding::Int64).unwrap(),
+            vec![0, 0, 0, 0, 0, 0, 0, 1]
+        );
+        assert!(decode_payload("128", AppliedEncoding::Int8).is_err());
+    }
+
+    #[test]
+    fn rejects_payloads_that_cannot_be_restored() {
+        assert!(decode_payload(r#"{"id":1}"#, AppliedEncoding::Avro).is_err());
+        assert!(decode_payload(r#"{"id":1}"#, AppliedEncoding::Protobuf).is_err());
+        assert!(decode_payload("a\u{fffd}b", AppliedEncoding::Lossy).is_err());
+        assert_eq!(decode_payload("ab", AppliedEncoding::Lossy).unwrap(), b"ab");
+        assert_eq!(
+            decode_payload(r#"{"id":1}"#, AppliedEncoding::Json).unwrap(),
+            br#"{"id":1}"#
+        );
+    }
+}
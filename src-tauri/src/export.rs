@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+use rdkafka::ClientConfig;
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+
+use crate::decoders::TopicDecoders;
+use crate::encoding::PayloadEncoding;
+use crate::filters::{MessageFilter, MessageMatcher};
+use crate::messages::{KafkaMessageResponse, PartitionRange};
+use crate::search::{create_topic_readers, scan_topic, SearchProgress};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    /// One message per line, as returned when listening
+    Jsonl,
+    /// partition, offset, timestamp, key, value and headers as a JSON object
+    Csv,
+}
+
+/// Where and what to export, as chosen in the export dialog
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportOptions {
+    pub path: String,
+    pub format: ExportFormat,
+    pub ranges: Option<Vec<PartitionRange>>,
+    pub filter: Option<MessageFilter>,
+    /// Used for the fields no decoder applies to
+    pub encoding: Option<PayloadEncoding>,
+}
+
+enum ExportWriter {
+    Jsonl(BufWriter<File>),
+    Csv(Box<csv::Writer<File>>),
+}
+
+impl ExportWriter {
+    fn new(path: &str, format: ExportFormat) -> Result<ExportWriter, String> {
+        let file = File::create(path)
+            .map_err(|err| format!("Could not create file {}: {}", path, err.to_string()))?;
+
+        match format {
+            ExportFormat::Jsonl => Ok(ExportWriter::Jsonl(BufWriter::new(file))),
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(file);
+                writer
+                    .write_record([
+                        "partition",
+                        "offset",
+                        "timestamp",
+                        "key",
+                        "value",
+                        "headers",
+                    ])
+                    .map_err(|err| format!("Could not write to {}: {}", path, err.to_string()))?;
+                Ok(ExportWriter::Csv(Box::new(writer)))
+            }
+        }
+    }
+
+    fn write(&mut self, message: &KafkaMessageResponse) -> Result<(), String> {
+        match self {
+            ExportWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, message).map_err(|err| err.to_string())?;
+                writer.write_all(b"\n").map_err(|err| err.to_string())
+            }
+            ExportWriter::Csv(writer) => {
+                let headers = match message.headers {
+                    Some(ref headers) => {
+                        serde_json::to_string(headers).map_err(|err| err.to_string())?
+                    }
+                    None => String::new(),
+                };
+
+                writer
+                    .write_record([
+                        message.partition.to_string().as_str(),
+                        message.offset.to_string().as_str(),
+                        message.timestamp.to_string().as_str(),
+                        message.key.as_deref().unwrap_or_default(),
+                        message.value.as_deref().unwrap_or_default(),
+                        headers.as_str(),
+                    ])
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        match self {
+            ExportWriter::Jsonl(writer) => writer.flush(),
+            ExportWriter::Csv(writer) => writer.flush(),
+        }
+        .map_err(|err| err.to_string())
+    }
+}
+
+/// Write the messages of the topic to a file as they are read, whole topic unless narrowed
+/// down by ranges or filter
+pub async fn export_messages(
+    window: Window,
+    common_config: ClientConfig,
+    topic: String,
+    options: ExportOptions,
+    decoders: &TopicDecoders<'_>,
+    id: String,
+) -> Result<SearchProgress, String> {
+    let ExportOptions {
+        path,
+        format,
+        ranges,
+        filter,
+        ..
+    } = options;
+    let matcher = MessageMatcher::from_filter(filter)?;
+    let readers = create_topic_readers(&common_config, &topic, ranges.as_deref())?;
+    let writer = Mutex::new(ExportWriter::new(&path, format)?);
+
+    let progress = scan_topic(
+        &window,
+        &readers,
+        decoders,
+        matcher.as_ref(),
+        "Export",
+        &id,
+        |_, message| {
+            writer
+                .lock()
+                .unwrap()
+                .write(&message)
+                .map_err(|err| format!("Could not write to {}: {}", path, err))
+        },
+    )
+    .await?;
+
+    writer
+        .lock()
+        .unwrap()
+        .flush()
+        .map_err(|err| format!("Could not write to {}: {}", path, err))?;
+
+    Ok(progress)
+}
+
+/// Write messages already in memory to a file, e.g. a materialized compacted topic
+pub fn write_messages_file(
+    path: &str,
+    format: ExportFormat,
+    messages: &[KafkaMessageResponse],
+) -> Result<(), String> {
+    let mut writer = ExportWriter::new(path, format)?;
+    for message in messages {
+        writer
+            .write(message)
+            .map_err(|err| format!("Could not write to {}: {}", path, err))?;
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("Could not write to {}: {}", path, err))
+}
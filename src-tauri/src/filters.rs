@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
+
+/// Conditions a message has to satisfy to be emitted, all the given ones have to match
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageFilter {
+    /// Regex searched in the key
+    pub key_pattern: Option<String>,
+    pub headers: Option<Vec<HeaderFilter>>,
+    pub value_contains: Option<String>,
+    /// JSONPath query on the value, e.g. `$.items[?@.sku == 'X']`, matching when it selects anything
+    pub value_json_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeaderFilter {
+    pub name: String,
+    /// Only the presence of the header is checked when not set
+    pub value: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FilterProgress {
+    pub scanned: u64,
+    pub matched: u64,
+}
+
+/// Filter compiled once when starting to listen
+pub struct MessageMatcher {
+    key_pattern: Option<Regex>,
+    headers: Vec<HeaderFilter>,
+    value_contains: Option<String>,
+    value_json_path: Option<JsonPath>,
+}
+
+impl MessageMatcher {
+    pub fn new(filter: MessageFilter) -> Result<MessageMatcher, String> {
+        let key_pattern =
+            match filter.key_pattern {
+                Some(ref pattern) => Some(Regex::new(pattern).map_err(|err| {
+                    format!("Invalid key pattern {}: {}", pattern, err.to_string())
+                })?),
+                None => None,
+            };
+
+        let value_json_path = match filter.value_json_path {
+            Some(ref path) => Some(
+                JsonPath::parse(path)
+                    .map_err(|err| format!("Invalid JSONPath {}: {}", path, err.to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(MessageMatcher {
+            key_pattern,
+            headers: filter.headers.unwrap_or_default(),
+            value_contains: filter.value_contains,
+            value_json_path,
+        })
+    }
+
+    /// Matcher of the filter when one is given, every message matches otherwise
+    pub fn from_filter(filter: Option<MessageFilter>) -> Result<Option<MessageMatcher>, String> {
+        filter.map(MessageMatcher::new).transpose()
+    }
+
+    /// Match the message as decoded for the frontend
+    pub fn matches(
+        &self,
+        key: Option<&str>,
+        headers: Option<&HashMap<String, Option<String>>>,
+        value: Option<&str>,
+    ) -> bool {
+        if let Some(ref pattern) = self.key_pattern {
+            if !key.map_or(false, |key| pattern.is_match(key)) {
+                return false;
+            }
+        }
+
+        for header_filter in &self.headers {
+            let header = headers.and_then(|headers| headers.get(&header_filter.name));
+            let is_match = match (header, &header_filter.value) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(header), Some(expected)) => header.as_deref() == Some(expected.as_str()),
+            };
+            if !is_match {
+                return false;
+            }
+        }
+
+        if let Some(ref substring) = self.value_contains {
+            if !value.map_or(false, |value| value.contains(substring.as_str())) {
+                return false;
+            }
+        }
+
+        if let Some(ref path) = self.value_json_path {
+            let json =
+                value.and_then(|value| serde_json::from_str::<serde_json::Value>(value).ok());
+            match json {
+                Some(json) => {
+                    if path.query(&json).is_empty() {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
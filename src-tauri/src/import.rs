@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, RwLock};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::Window;
+use tokio::time::{Duration, Instant};
+
+use crate::encoding::{decode_payload, AppliedEncoding};
+use crate::messages::{serialize_payload, MessageCodecs, MessageFormat};
+
+// Records waiting for their delivery report, bounds the memory used by big files
+const MAX_IN_FLIGHT: usize = 1000;
+
+/// A line of the file, in the format written by the JSONL export
+#[derive(Deserialize, Debug)]
+struct ImportRecord {
+    headers: Option<HashMap<String, Option<String>>>,
+    key: Option<String>,
+    value: Option<String>,
+    partition: Option<i32>,
+    headers_encoding: Option<HashMap<String, AppliedEncoding>>,
+    key_encoding: Option<AppliedEncoding>,
+    value_encoding: Option<AppliedEncoding>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ImportKey {
+    Keep,
+    Remove,
+    Constant {
+        key: String,
+    },
+    /// Field of the JSON value pointed by a JSON Pointer, e.g. `/order/id`
+    ValueField {
+        pointer: String,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ImportHeaders {
+    Keep,
+    /// Add the given headers, overriding the ones with the same name
+    Merge {
+        headers: HashMap<String, Option<String>>,
+    },
+    Replace {
+        headers: HashMap<String, Option<String>>,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ImportOptions {
+    /// Produce every record to the partition it was exported from instead of using the partitioner
+    pub keep_partition: bool,
+    pub key: Option<ImportKey>,
+    pub headers: Option<ImportHeaders>,
+    pub key_format: Option<MessageFormat>,
+    pub value_format: Option<MessageFormat>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ImportProgress {
+    pub read: u64,
+    pub delivered: u64,
+    pub failed: u64,
+}
+
+/// Produce every line of a JSONL file to the topic, progress is emitted as
+/// `onImportProgress-{id}` and the import is stopped by `offImport-{id}`
+pub async fn import_messages(
+    window: Window,
+    producer: &FutureProducer,
+    topic: String,
+    path: String,
+    options: ImportOptions,
+    codecs: MessageCodecs<'_>,
+    id: String,
+) -> Result<ImportProgress, String> {
+    let file = File::open(&path)
+        .map_err(|err| format!("Could not open file {}: {}", path, err.to_string()))?;
+
+    let keep_importing = Arc::new(RwLock::new(true));
+    let keep_importing_clone = keep_importing.clone();
+    window.once(format!("offImport-{}", id), move |_| {
+        *keep_importing_clone.write().unwrap() = false;
+    });
+
+    let mut progress = ImportProgress::default();
+    let mut last_progress = Instant::now();
+    let mut in_flight = FuturesUnordered::new();
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        if !*keep_importing.read().unwrap() {
+            break;
+        }
+
+        let line =
+            line.map_err(|err| format!("Could not read file {}: {}", path, err.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        progress.read += 1;
+
+        // A bad line should not stop the whole import
+        let delivery = match send_record(
+            producer,
+            &topic,
+            &line,
+            &options,
+            codecs,
+            &mut in_flight,
+            &mut progress,
+        )
+        .await
+        {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                log::warn!("Could not import line {} of {}: {}", index + 1, path, err);
+                progress.failed += 1;
+                continue;
+            }
+        };
+        in_flight.push(delivery);
+
+        if in_flight.len() >= MAX_IN_FLIGHT {
+            if let Some(result) = in_flight.next().await {
+                count_delivery(result, &mut progress);
+            }
+        }
+
+        if last_progress.elapsed() >= Duration::from_secs(1) {
+            window
+                .emit(&format!("onImportProgress-{}", id), progress.clone())
+                .unwrap();
+            last_progress = Instant::now();
+        }
+    }
+
+    while let Some(result) = in_flight.next().await {
+        count_delivery(result, &mut progress);
+    }
+
+    window
+        .emit(&format!("onImportProgress-{}", id), progress.clone())
+        .unwrap();
+
+    Ok(progress)
+}
+
+async fn send_record(
+    producer: &FutureProducer,
+    topic: &str,
+    line: &str,
+    options: &ImportOptions,
+    codecs: MessageCodecs<'_>,
+    in_flight: &mut FuturesUnordered<DeliveryFuture>,
+    progress: &mut ImportProgress,
+) -> Result<DeliveryFuture, String> {
+    let record: ImportRecord =
+        serde_json::from_str(line).map_err(|err| format!("Invalid record: {}", err.to_string()))?;
+
+    let key = match options.key.as_ref().unwrap_or(&ImportKey::Keep) {
+        ImportKey::Keep => record.key.clone(),
+        ImportKey::Remove => None,
+        ImportKey::Constant { key } => Some(key.clone()),
+        ImportKey::ValueField { pointer } => {
+            let value: Value = record
+                .value
+                .as_deref()
+                .and_then(|value| serde_json::from_str(value).ok())
+                .ok_or("Value is not a JSON document to take the key from")?;
+            let field = value
+                .pointer(pointer)
+                .ok_or(format!("Field {} not found in the value", pointer))?;
+            Some(match field {
+                Value::String(field) => field.clone(),
+                field => field.to_string(),
+            })
+        }
+    };
+    // Only keys taken as they are from the record keep their original encoding
+    let key_encoding = match options.key {
+        None | Some(ImportKey::Keep) => record.key_encoding,
+        _ => None,
+    };
+
+    let key = match key {
+        Some(key) => Some(to_bytes(key, key_encoding, options.key_format.as_ref(), codecs).await?),
+        None => None,
+    };
+    let value = match record.value {
+        Some(value) => Some(
+            to_bytes(
+                value,
+                record.value_encoding,
+                options.value_format.as_ref(),
+                codecs,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let headers_encoding = record.headers_encoding.unwrap_or_default();
+    let mut headers = HashMap::new();
+    let keep_record_headers = !matches!(options.headers, Some(ImportHeaders::Replace { .. }));
+    if keep_record_headers {
+        for (header_key, header_value) in record.headers.unwrap_or_default() {
+            let header_value = match header_value {
+                Some(header_value) => Some(decode_payload(
+                    &header_value,
+                    headers_encoding
+                        .get(&header_key)
+                        .copied()
+                        .unwrap_or(AppliedEncoding::Utf8),
+                )?),
+                None => None,
+            };
+            headers.insert(header_key, header_value);
+        }
+    }
+    if let Some(
+        ImportHeaders::Merge { headers: rewritten } | ImportHeaders::Replace { headers: rewritten },
+    ) = &options.headers
+    {
+        for (header_key, header_value) in rewritten {
+            headers.insert(
+                header_key.clone(),
+                header_value.as_ref().map(|value| value.as_bytes().to_vec()),
+            );
+        }
+    }
+
+    let mut headers_to_send = OwnedHeaders::new();
+    for (header_key, header_value) in &headers {
+        headers_to_send = headers_to_send.insert(Header {
+            key: header_key,
+            value: header_value.as_ref(),
+        });
+    }
+
+    loop {
+        let mut future_record = FutureRecord::to(topic).headers(headers_to_send.clone());
+        if let Some(ref key) = key {
+            future_record = future_record.key(key);
+        }
+        if let Some(ref value) = value {
+            future_record = future_record.payload(value);
+        }
+        if options.keep_partition {
+            if let Some(partition) = record.partition {
+                future_record = future_record.partition(partition);
+            }
+        }
+
+        match producer.send_result(future_record) {
+            Ok(delivery) => return Ok(delivery),
+            // Wait for some deliveries to free the producer queue
+            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                match in_flight.next().await {
+                    Some(result) => count_delivery(result, progress),
+                    None => tokio::time::sleep(Duration::from_millis(100)).await,
+                }
+            }
+            Err((err, _)) => {
+                return Err(format!(
+                    "Error while sending the message: {}",
+                    err.to_string()
+                ))
+            }
+        }
+    }
+}
+
+async fn to_bytes(
+    text: String,
+    encoding: Option<AppliedEncoding>,
+    format: Option<&MessageFormat>,
+    codecs: MessageCodecs<'_>,
+) -> Result<Vec<u8>, String> {
+    match format {
+        Some(format) => serialize_payload(text, Some(format), codecs).await,
+        None => decode_payload(&text, encoding.unwrap_or(AppliedEncoding::Utf8)),
+    }
+}
+
+fn count_delivery(
+    result: <DeliveryFuture as futures::Future>::Output,
+    progress: &mut ImportProgress,
+) {
+    match result {
+        Ok(Ok(_)) => progress.delivered += 1,
+        Ok(Err((err, _))) => {
+            log::warn!("Could not deliver imported message: {}", err);
+            progress.failed += 1;
+        }
+        Err(_) => progress.failed += 1,
+    }
+}
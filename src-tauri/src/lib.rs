@@ -6,6 +6,25 @@ pub mod storage;
 pub mod connection;
 pub mod logs;
 pub mod utils;
+pub mod encoding;
+pub mod schema_registry;
+pub mod protobuf;
+pub mod decoders;
+pub mod filters;
+pub mod search;
+pub mod export;
+pub mod import;
+pub mod copy;
+pub mod autosend;
+pub mod templates;
+pub mod transactions;
+pub mod pagination;
+pub mod sessions;
+pub mod compacted;
+pub mod tombstones;
+pub mod records;
+pub mod replies;
+pub mod producers;
 
 // Re-export
 pub use crate::groups::*;
@@ -15,4 +34,23 @@ pub use crate::state::*;
 pub use crate::storage::*;
 pub use crate::connection::*;
 pub use crate::logs::*;
-pub use crate::utils::*;
\ No newline at end of file
+pub use crate::utils::*;
+pub use crate::encoding::*;
+pub use crate::schema_registry::*;
+pub use crate::protobuf::*;
+pub use crate::decoders::*;
+pub use crate::filters::*;
+pub use crate::search::*;
+pub use crate::export::*;
+pub use crate::import::*;
+pub use crate::copy::*;
+pub use crate::autosend::*;
+pub use crate::templates::*;
+pub use crate::transactions::*;
+pub use crate::pagination::*;
+pub use crate::sessions::*;
+pub use crate::compacted::*;
+pub use crate::tombstones::*;
+pub use crate::records::*;
+pub use crate::replies::*;
+pub use crate::producers::*;
\ No newline at end of file
@@ -3,8 +3,8 @@
     windows_subsystem = "windows"
 )]
 
-use kafka_panel::{logs, init_storage, init_kafka};
-use tauri::Manager;
+use kafka_panel::{logs, init_storage, init_kafka, stop_window_sessions, KafkaState};
+use tauri::{Manager, WindowEvent};
 
 mod commands;
 
@@ -21,6 +21,16 @@ fn main() {
 
             Ok(())
         })
+        .on_window_event(|event| {
+            // A closed window never sends the offMessage of its listen sessions
+            if let WindowEvent::Destroyed = event.event() {
+                let sessions = event.window().state::<KafkaState>().listen_sessions.clone();
+                let window = event.window().label().to_owned();
+                tauri::async_runtime::spawn(async move {
+                    stop_window_sessions(&sessions, &window).await;
+                });
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             // Generic commands
             commands::get_env_command,
@@ -38,9 +48,23 @@ fn main() {
             commands::get_topics_watermark_command,
             commands::create_topic_command,
             commands::delete_topic_command,
+            commands::truncate_topic_command,
             // Message commands
             commands::listen_messages_command,
+            commands::get_messages_page_command,
+            commands::search_messages_command,
+            commands::materialize_topic_command,
+            commands::tombstone_keys_command,
+            commands::export_messages_command,
+            commands::import_messages_command,
+            commands::copy_messages_command,
             commands::send_message_command,
+            commands::send_transaction_command,
+            commands::request_reply_command,
+            // Autosend commands
+            commands::start_autosend_command,
+            commands::stop_autosend_command,
+            commands::get_autosends_command,
             // Store commands
             commands::save_in_store_command,
             commands::get_from_store_command,
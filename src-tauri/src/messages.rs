@@ -1,32 +1,122 @@
 use rdkafka::consumer::{Consumer, StreamConsumer};
 use rdkafka::message::{BorrowedMessage, Header, Headers, OwnedHeaders};
-use rdkafka::producer::{FutureProducer, FutureRecord};
-use rdkafka::util::Timeout;
 use rdkafka::{Message, Offset, TopicPartitionList};
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use tauri::Window;
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
+
+use crate::decoders::TopicDecoders;
+use crate::encoding::{AppliedEncoding, PayloadEncoding};
+use crate::filters::{FilterProgress, MessageFilter, MessageMatcher};
+use crate::producers::{ReportingProducer, SerializedRecord};
+use crate::protobuf::{encode_protobuf, ProtobufRegistry};
+use crate::schema_registry::{encode_avro, SchemaRegistryClient};
+
+/// Registries configured for the connection, used to (de)serialize keys and values
+#[derive(Clone, Copy)]
+pub struct MessageCodecs<'a> {
+    pub schema_registry: Option<&'a SchemaRegistryClient>,
+    pub protobuf: Option<&'a ProtobufRegistry>,
+}
+
+/// Offsets to read from a single partition, both ends included
+#[derive(Deserialize, Debug, Clone)]
+pub struct PartitionRange {
+    pub partition: i32,
+    /// Where the listen would otherwise start when not set
+    pub start_offset: Option<i64>,
+    /// Keep tailing the partition when not set
+    pub end_offset: Option<i64>,
+}
+
+/// Which records of transactional producers are received while listening
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// Every record, including the ones of aborted and still open transactions
+    ReadUncommitted,
+    /// Only committed records, what transactional consumers see
+    ReadCommitted,
+}
+
+impl IsolationLevel {
+    pub fn as_config(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "read_uncommitted",
+            IsolationLevel::ReadCommitted => "read_committed",
+        }
+    }
+}
+
+/// What a listen reads and shows, as chosen in the messages view
+#[derive(Deserialize, Debug, Clone)]
+pub struct ListenOptions {
+    /// Last messages of every partition to start from
+    pub messages_number: i64,
+    /// Start from the first message at or after this timestamp instead of the last messages
+    pub from_timestamp: Option<i64>,
+    pub ranges: Option<Vec<PartitionRange>>,
+    pub filter: Option<MessageFilter>,
+    pub isolation_level: Option<IsolationLevel>,
+    /// Used for the fields no decoder applies to
+    pub encoding: Option<PayloadEncoding>,
+}
+
+/// Offsets of a partition without a record between two records received by a read_committed
+/// listen, both ends included. What they held isn't known: records of aborted transactions,
+/// transaction markers or records removed by compaction
+#[derive(Serialize, Debug, Clone)]
+pub struct OffsetGap {
+    pub partition: i32,
+    pub start_offset: i64,
+    pub end_offset: i64,
+}
 
 #[derive(Serialize, Clone)]
 pub struct KafkaMessageResponse {
-    headers: Option<HashMap<String, Option<String>>>,
-    value: Option<String>,
-    key: String,
-    offset: i64,
-    partition: i32,
-    timestamp: i64,
+    pub headers: Option<HashMap<String, Option<String>>>,
+    pub value: Option<String>,
+    pub key: Option<String>,
+    pub offset: i64,
+    pub partition: i32,
+    pub timestamp: i64,
+    pub headers_encoding: Option<HashMap<String, AppliedEncoding>>,
+    pub value_encoding: Option<AppliedEncoding>,
+    pub key_encoding: Option<AppliedEncoding>,
+}
+
+impl KafkaMessageResponse {
+    pub fn matches(&self, matcher: &MessageMatcher) -> bool {
+        matcher.matches(
+            self.key.as_deref(),
+            self.headers.as_ref(),
+            self.value.as_deref(),
+        )
+    }
 }
 
 pub async fn listen_messages(
     window: Window,
     consumer: &StreamConsumer,
     topic: String,
-    messages_number: i64,
+    options: ListenOptions,
+    decoders: &TopicDecoders<'_>,
+    keep_listening: Arc<RwLock<bool>>,
     id: String,
 ) -> Result<(), String> {
+    let ListenOptions {
+        messages_number,
+        from_timestamp,
+        ranges,
+        filter,
+        isolation_level,
+        ..
+    } = options;
+    let matcher = MessageMatcher::from_filter(filter)?;
+    let matcher = matcher.as_ref();
+
     // Manually fetch metadata and assign partition so we don't fetch using our consumer group
     let metadata = consumer
         .fetch_metadata(Some(&topic), Duration::from_secs(30))
@@ -37,9 +127,36 @@ pub async fn listen_messages(
                 err.to_string()
             )
         })?;
+    let topic_partitions: Vec<i32> = metadata
+        .topics()
+        .first()
+        .unwrap()
+        .partitions()
+        .iter()
+        .map(|partition| partition.id())
+        .collect();
+
+    // Only the partitions of the ranges are read when given, all of them otherwise
+    let partitions = match ranges {
+        Some(ref ranges) => {
+            let mut partitions = vec![];
+            for range in ranges {
+                if !topic_partitions.contains(&range.partition) {
+                    return Err(format!(
+                        "Partition {} not found in topic: {}",
+                        range.partition, topic
+                    ));
+                }
+                partitions.push(range.partition);
+            }
+            partitions
+        }
+        None => topic_partitions,
+    };
+
     let mut tpl = TopicPartitionList::new();
-    for partition in metadata.topics().get(0).unwrap().partitions() {
-        tpl.add_partition(&topic, partition.id());
+    for partition in &partitions {
+        tpl.add_partition(&topic, *partition);
     }
     consumer.assign(&tpl).map_err(|err| {
         format!(
@@ -49,61 +166,89 @@ pub async fn listen_messages(
         )
     })?;
 
-    // Seek the latest watermark minus messages number to get only the last messages
-    for partition in metadata.topics().get(0).unwrap().partitions() {
-        let (_, high) = consumer
-            .fetch_watermarks(&topic, partition.id(), Duration::from_secs(30))
+    let mut start_offsets = match from_timestamp {
+        Some(timestamp) => get_timestamp_offsets(consumer, &topic, &partitions, timestamp)?,
+        None => get_latest_offsets(consumer, &topic, &partitions, messages_number)?,
+    };
+
+    let mut end_offsets = HashMap::new();
+    let mut finished_partitions = HashSet::new();
+    for range in ranges.unwrap_or_default() {
+        let (low, high) = consumer
+            .fetch_watermarks(&topic, range.partition, Duration::from_secs(30))
             .map_err(|err| {
                 format!(
-                    "Could not seek partition offset in topic: {}, partition: {}\n\nError: {}",
+                    "Could not fetch watermarks for topic: {}, partition: {}\n\nError: {}",
                     topic,
-                    partition.id(),
+                    range.partition,
                     err.to_string()
                 )
             })?;
 
-        let seek_start = high - messages_number;
-        let offset_start = if seek_start > 0 {
-            Offset::Offset(seek_start)
-        } else {
-            Offset::Beginning
+        let mut start = match start_offsets
+            .iter()
+            .find(|(partition, _)| *partition == range.partition)
+        {
+            Some((_, Offset::Offset(offset))) => *offset,
+            Some((_, Offset::End)) => high,
+            _ => low,
         };
-        
-        // Workaround system for the Erroneus state error
-        let mut success = false;
-        let mut attempts = 0;
-        while !success {
-            success = consumer.seek(&topic, partition.id(), offset_start, Duration::from_secs(30))
-                .map(|_| true)
-                .or_else(|error| {
-                    if error.to_string() == "Seek error: Local: Erroneous state" && attempts < 5 {
-                        return Ok(false);
-                    }
-    
-                    Err(error)
-                })
-                .map_err(|err| {
-                    format!(
-                        "Could not seek partition offset in topic: {}, partition: {}, offset: {:?}\n\nError: {}",
-                        topic,
-                        partition.id(),
-                        offset_start,
-                        err.to_string()
-                    )
-                })?;
-
-            attempts += 1;
-            thread::sleep(Duration::from_millis(100));
+        if let Some(start_offset) = range.start_offset {
+            start = start_offset.max(low);
+            for (partition, offset) in start_offsets.iter_mut() {
+                if *partition == range.partition {
+                    *offset = Offset::Offset(start);
+                }
+            }
+        }
+
+        if let Some(end_offset) = range.end_offset {
+            // Do not wait for messages that are not there yet
+            let end_offset = end_offset.min(high - 1);
+            if end_offset < start {
+                finished_partitions.insert(range.partition);
+            }
+            end_offsets.insert(range.partition, end_offset);
         }
     }
 
-    let keep_listening = Arc::new(RwLock::new(true));
+    // Listening ends by itself only when every partition has an end offset
+    let is_bounded = end_offsets.len() == partitions.len();
+
+    // Last offset seen for each partition, gaps between offsets are emitted
+    let mark_gaps = isolation_level == Some(IsolationLevel::ReadCommitted);
+    let mut last_offsets = HashMap::new();
+    for (partition, offset_start) in start_offsets.iter() {
+        if let Offset::Offset(offset) = offset_start {
+            last_offsets.insert(*partition, offset - 1);
+        }
+    }
+
+    for (partition, offset_start) in start_offsets {
+        seek_partition(consumer, &topic, partition, offset_start)?;
+    }
+
     let keep_listening_clone = keep_listening.clone();
     window.once(format!("offMessage-{}", id), move |_| {
         *keep_listening_clone.write().unwrap() = false;
     });
 
+    let mut progress = FilterProgress::default();
+    let mut last_progress = Instant::now();
+
     while *keep_listening.read().unwrap() {
+        // Let the frontend know the filter is still scanning even when nothing matches
+        if matcher.is_some() && last_progress.elapsed() >= Duration::from_secs(1) {
+            window
+                .emit(&format!("onProgress-{}", id), progress.clone())
+                .unwrap();
+            last_progress = Instant::now();
+        }
+
+        if is_bounded && finished_partitions.len() == end_offsets.len() {
+            break;
+        }
+
         let timeout = Duration::from_secs(3);
         let message = match tokio::time::timeout(timeout, consumer.recv()).await {
             Ok(Ok(message)) => Ok(Some(message)),
@@ -113,63 +258,290 @@ pub async fn listen_messages(
 
         match message {
             Some(message) => {
-                let message_result = process_message(&message).map_err(|err| {
-                    format!(
-                        "Could not process message for topic: {}, partition: {}, offset: {}\n\nError: {}",
-                        message.topic(),
-                        message.partition(),
-                        message.offset(),
-                        err
-                    )
-                })?;
+                if let Some(end_offset) = end_offsets.get(&message.partition()) {
+                    if message.offset() >= *end_offset {
+                        finished_partitions.insert(message.partition());
+                        pause_partition(consumer, &topic, message.partition())?;
+                    }
+                    if message.offset() > *end_offset {
+                        continue;
+                    }
+                }
+
+                if mark_gaps {
+                    if let Some(last_offset) =
+                        last_offsets.insert(message.partition(), message.offset())
+                    {
+                        emit_offset_gap(
+                            &window,
+                            &id,
+                            message.partition(),
+                            last_offset,
+                            message.offset(),
+                        );
+                    }
+                }
+
+                let message_result = process_message(&message, decoders)
+                    .await
+                    .map_err(|err| {
+                        format!(
+                            "Could not process message for topic: {}, partition: {}, offset: {}\n\nError: {}",
+                            message.topic(),
+                            message.partition(),
+                            message.offset(),
+                            err
+                        )
+                    })?;
+
+                if let Some(matcher) = matcher {
+                    progress.scanned += 1;
+                    if !message_result.matches(matcher) {
+                        continue;
+                    }
+                    progress.matched += 1;
+                }
 
                 window
                     .emit(&format!("onMessage-{}", id), message_result)
                     .unwrap();
             }
-            None => {}
+            None => {
+                // The last offsets of a range might never be delivered, e.g. transaction markers
+                for (partition, end_offset) in end_offsets.iter() {
+                    if get_position(consumer, &topic, *partition)? > *end_offset {
+                        finished_partitions.insert(*partition);
+                    }
+                }
+
+                // A gap at the end of the partition is never followed by a record
+                if mark_gaps {
+                    for (partition, last_offset) in last_offsets.iter_mut() {
+                        let position = get_position(consumer, &topic, *partition)?;
+                        let position = match end_offsets.get(partition) {
+                            Some(end_offset) => position.min(end_offset + 1),
+                            None => position,
+                        };
+                        if position > *last_offset + 1 {
+                            emit_offset_gap(&window, &id, *partition, *last_offset, position);
+                            *last_offset = position - 1;
+                        }
+                    }
+                }
+            }
         }
     }
 
-    consumer.unsubscribe();
+    if matcher.is_some() {
+        window
+            .emit(&format!("onProgress-{}", id), progress)
+            .unwrap();
+    }
+
+    Ok(())
+}
+
+/// Seek the latest watermark minus messages number to get only the last messages
+fn get_latest_offsets(
+    consumer: &StreamConsumer,
+    topic: &str,
+    partitions: &[i32],
+    messages_number: i64,
+) -> Result<Vec<(i32, Offset)>, String> {
+    let mut offsets = vec![];
+    for partition in partitions {
+        let (_, high) = consumer
+            .fetch_watermarks(topic, *partition, Duration::from_secs(30))
+            .map_err(|err| {
+                format!(
+                    "Could not seek partition offset in topic: {}, partition: {}\n\nError: {}",
+                    topic,
+                    partition,
+                    err.to_string()
+                )
+            })?;
+
+        let seek_start = high - messages_number;
+        let offset_start = if seek_start > 0 {
+            Offset::Offset(seek_start)
+        } else {
+            Offset::Beginning
+        };
+        offsets.push((*partition, offset_start));
+    }
+
+    Ok(offsets)
+}
+
+/// First offset of each partition with a timestamp greater or equal than the given one (in millis),
+/// partitions without such a message start from the end
+fn get_timestamp_offsets(
+    consumer: &StreamConsumer,
+    topic: &str,
+    partitions: &[i32],
+    timestamp: i64,
+) -> Result<Vec<(i32, Offset)>, String> {
+    let mut tpl = TopicPartitionList::new();
+    for partition in partitions {
+        tpl.add_partition_offset(topic, *partition, Offset::Offset(timestamp))
+            .unwrap();
+    }
+
+    let offsets = consumer
+        .offsets_for_times(tpl, Duration::from_secs(30))
+        .map_err(|err| {
+            format!(
+                "Could not get offsets for timestamp {} in topic: {}\n\nError: {}",
+                timestamp,
+                topic,
+                err.to_string()
+            )
+        })?;
+
+    Ok(offsets
+        .elements()
+        .iter()
+        .map(|element| match element.offset() {
+            Offset::Offset(offset) => (element.partition(), Offset::Offset(offset)),
+            _ => (element.partition(), Offset::End),
+        })
+        .collect())
+}
+
+fn seek_partition(
+    consumer: &StreamConsumer,
+    topic: &str,
+    partition: i32,
+    offset_start: Offset,
+) -> Result<(), String> {
+    // Workaround system for the Erroneus state error
+    let mut success = false;
+    let mut attempts = 0;
+    while !success {
+        success = consumer
+            .seek(topic, partition, offset_start, Duration::from_secs(30))
+            .map(|_| true)
+            .or_else(|error| {
+                if error.to_string() == "Seek error: Local: Erroneous state" && attempts < 5 {
+                    return Ok(false);
+                }
+
+                Err(error)
+            })
+            .map_err(|err| {
+                format!(
+                    "Could not seek partition offset in topic: {}, partition: {}, offset: {:?}\n\nError: {}",
+                    topic,
+                    partition,
+                    offset_start,
+                    err.to_string()
+                )
+            })?;
+
+        attempts += 1;
+        thread::sleep(Duration::from_millis(100));
+    }
 
     Ok(())
 }
 
-fn process_message(message: &BorrowedMessage) -> Result<KafkaMessageResponse, String> {
-    let headers = match message.headers() {
+/// Emits `onOffsetGap-{id}` for the offsets between two records of a partition. Gaps of a single
+/// offset are left out, every committed transaction ends with one for its marker
+fn emit_offset_gap(window: &Window, id: &str, partition: i32, last_offset: i64, next_offset: i64) {
+    if next_offset - last_offset > 2 {
+        window
+            .emit(
+                &format!("onOffsetGap-{}", id),
+                OffsetGap {
+                    partition,
+                    start_offset: last_offset + 1,
+                    end_offset: next_offset - 1,
+                },
+            )
+            .unwrap();
+    }
+}
+
+pub(crate) fn pause_partition(
+    consumer: &StreamConsumer,
+    topic: &str,
+    partition: i32,
+) -> Result<(), String> {
+    let mut tpl = TopicPartitionList::new();
+    tpl.add_partition(topic, partition);
+    consumer.pause(&tpl).map_err(|err| {
+        format!(
+            "Could not pause topic: {}, partition: {}\n\nError: {}",
+            topic,
+            partition,
+            err.to_string()
+        )
+    })
+}
+
+/// Next offset the consumer will read from the partition, -1 when nothing was read yet
+pub(crate) fn get_position(
+    consumer: &StreamConsumer,
+    topic: &str,
+    partition: i32,
+) -> Result<i64, String> {
+    let position = consumer.position().map_err(|err| {
+        format!(
+            "Could not get consumer position for topic: {}\n\nError: {}",
+            topic,
+            err.to_string()
+        )
+    })?;
+
+    Ok(
+        match position
+            .find_partition(topic, partition)
+            .map(|element| element.offset())
+        {
+            Some(Offset::Offset(offset)) => offset,
+            _ => -1,
+        },
+    )
+}
+
+pub(crate) async fn process_message(
+    message: &BorrowedMessage<'_>,
+    decoders: &TopicDecoders<'_>,
+) -> Result<KafkaMessageResponse, String> {
+    let (headers, headers_encoding) = match message.headers() {
         Some(headers) => {
             let mut headers_map = HashMap::new();
+            let mut encodings_map = HashMap::new();
             for header in headers.iter() {
                 let value = match header.value {
                     Some(value) => {
-                        let stringified_value =
-                            std::str::from_utf8(value).map_err(|err| err.to_string())?;
-                        Some(stringified_value.to_owned())
+                        let (encoded_value, applied_encoding) = decoders.decode_header(value).await;
+                        encodings_map.insert(header.key.to_string(), applied_encoding);
+                        Some(encoded_value)
                     }
                     None => None,
                 };
                 headers_map.insert(header.key.to_string(), value);
             }
-            Some(headers_map)
+            (Some(headers_map), Some(encodings_map))
         }
-        None => None,
+        None => (None, None),
     };
 
-    let key = match message.key() {
+    let (key, key_encoding) = match message.key() {
         Some(key) => {
-            let stringified_key = std::str::from_utf8(key).map_err(|err| err.to_string())?;
-            stringified_key.to_owned()
+            let (encoded_key, applied_encoding) = decoders.decode_key(key).await;
+            (Some(encoded_key), Some(applied_encoding))
         }
-        None => return Err("Invalid message with no key".to_owned()),
+        None => (None, None),
     };
 
-    let value = match message.payload() {
+    let (value, value_encoding) = match message.payload() {
         Some(value) => {
-            let stringified_value = std::str::from_utf8(value).map_err(|err| err.to_string())?;
-            Some(stringified_value.to_owned())
+            let (encoded_value, applied_encoding) = decoders.decode_value(value).await;
+            (Some(encoded_value), Some(applied_encoding))
         }
-        None => None,
+        None => (None, None),
     };
 
     let timestamp_millis = message
@@ -184,45 +556,108 @@ fn process_message(message: &BorrowedMessage) -> Result<KafkaMessageResponse, St
         offset: message.offset(),
         partition: message.partition(),
         timestamp: timestamp_millis,
+        headers_encoding,
+        value_encoding,
+        key_encoding,
     })
 }
 
-pub async fn send_message(
-    producer: &FutureProducer,
-    topic: String,
-    headers: Option<HashMap<String, Option<&str>>>,
-    key: String,
-    value: Option<String>,
-) -> Result<(), String> {
-    let mut record = FutureRecord::to(&topic)
-        .key(&key);
-
-    #[allow(unused)]
-    let mut extracted_value = "".to_owned();
-    if value.is_some() {
-        extracted_value = value.unwrap();
-        record = record.payload(&extracted_value);
+/// Where a produced message ended up
+#[derive(Serialize, Debug, Clone)]
+pub struct DeliveryReport {
+    pub partition: i32,
+    pub offset: i64,
+    /// Timestamp stored by the broker, the producer create time unless the topic uses
+    /// LogAppendTime. Only reported for single messages
+    pub timestamp: Option<i64>,
+}
+
+/// Serialization applied to the key or value before producing, plain UTF-8 when not given
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum MessageFormat {
+    /// JSON document encoded with a schema registry subject, in the Confluent wire format
+    Avro {
+        subject: String,
+        version: Option<i32>,
+    },
+    /// JSON document encoded as the given protobuf message, Confluent framed when a schema id is given
+    Protobuf {
+        message: String,
+        schema_id: Option<u32>,
+    },
+}
+
+/// Message to produce, the formats serialize the key and value when given
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutgoingMessage {
+    pub topic: String,
+    pub headers: Option<HashMap<String, Option<String>>>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub key_format: Option<MessageFormat>,
+    pub value_format: Option<MessageFormat>,
+    /// Chosen by the partitioner configured for the connection when not set
+    pub partition: Option<i32>,
+}
+
+pub(crate) async fn serialize_payload(
+    payload: String,
+    format: Option<&MessageFormat>,
+    codecs: MessageCodecs<'_>,
+) -> Result<Vec<u8>, String> {
+    match format {
+        None => Ok(payload.into_bytes()),
+        Some(MessageFormat::Avro { subject, version }) => {
+            let schema_registry = codecs
+                .schema_registry
+                .ok_or("Schema registry not configured for this connection")?;
+            let json = serde_json::from_str(&payload)
+                .map_err(|err| format!("Invalid JSON to encode as avro: {}", err.to_string()))?;
+            encode_avro(schema_registry, subject, *version, json).await
+        }
+        Some(MessageFormat::Protobuf { message, schema_id }) => {
+            let descriptor = codecs
+                .protobuf
+                .and_then(|protobuf| protobuf.get_message(message))
+                .ok_or(format!(
+                    "Protobuf message {} not found in the connection descriptor sets",
+                    message
+                ))?;
+            encode_protobuf(&descriptor, &payload, *schema_id)
+        }
     }
+}
 
-    let mut headers_to_send = OwnedHeaders::new();
-    match headers {
-        Some(headers) => {
-            for (header_key, header_value) in headers {
-                headers_to_send = headers_to_send.insert(Header {
-                    key: &header_key,
-                    value: header_value,
-                });
-            }
-        },
-        None => {}
+pub async fn send_message(
+    producer: &ReportingProducer,
+    message: OutgoingMessage,
+    codecs: MessageCodecs<'_>,
+) -> Result<DeliveryReport, String> {
+    let key = match message.key {
+        Some(key) => Some(serialize_payload(key, message.key_format.as_ref(), codecs).await?),
+        None => None,
+    };
+    let value = match message.value {
+        Some(value) => Some(serialize_payload(value, message.value_format.as_ref(), codecs).await?),
+        None => None,
+    };
+
+    let mut headers = OwnedHeaders::new();
+    for (header_key, header_value) in message.headers.iter().flatten() {
+        headers = headers.insert(Header {
+            key: header_key,
+            value: header_value.as_deref(),
+        });
     }
-    record = record.headers(headers_to_send);
 
-    // FIXME: messages with same key sent by another system (kafkaJs, akhq) end up in different partitions
-    producer
-        .send(record, Timeout::Never)
-        .await
-        .map_err(|(err, _)| format!("Error while sending the message: {}", err.to_string()))?;
+    // Keyless records are left to the partitioner, like round-robin producers do
+    let record = SerializedRecord {
+        headers,
+        key,
+        value,
+        partition: message.partition,
+    };
 
-    Ok(())
+    producer.send(&message.topic, &record).await
 }
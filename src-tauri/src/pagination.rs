@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+use crate::decoders::TopicDecoders;
+use crate::messages::{get_position, pause_partition, process_message, KafkaMessageResponse};
+use crate::search::create_reader_consumer;
+
+/// Where the previous page of a partition ends, the offset itself is excluded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartitionCursor {
+    pub partition: i32,
+    pub offset: i64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MessagesPage {
+    /// Newest first
+    pub messages: Vec<KafkaMessageResponse>,
+    /// To be given back to get the page before this one
+    pub cursors: Vec<PartitionCursor>,
+    pub has_more: bool,
+}
+
+/// Page of the messages preceding the cursors, from the end of the topic when not given.
+/// Partitions are merged by timestamp while keeping the offset order of each partition,
+/// so that the next page starts exactly where this one ends
+pub async fn get_previous_page(
+    common_config: &ClientConfig,
+    topic: &str,
+    cursors: Option<Vec<PartitionCursor>>,
+    page_size: i64,
+    decoders: &TopicDecoders<'_>,
+) -> Result<MessagesPage, String> {
+    if page_size <= 0 {
+        return Err("Page size must be greater than zero".into());
+    }
+
+    let consumer = create_reader_consumer(common_config)?;
+    let cursors = match cursors {
+        Some(cursors) => cursors,
+        None => {
+            let metadata = consumer
+                .fetch_metadata(Some(topic), Duration::from_secs(30))
+                .map_err(|err| {
+                    format!(
+                        "Could not fetch topic metadada for topic: {}\n\nError: {}",
+                        topic,
+                        err.to_string()
+                    )
+                })?;
+            metadata
+                .topics()
+                .first()
+                .unwrap()
+                .partitions()
+                .iter()
+                .map(|partition| PartitionCursor {
+                    partition: partition.id(),
+                    offset: i64::MAX,
+                })
+                .collect()
+        }
+    };
+
+    let mut pages = vec![];
+    for cursor in cursors {
+        let (low, high) = consumer
+            .fetch_watermarks(topic, cursor.partition, Duration::from_secs(30))
+            .map_err(|err| {
+                format!(
+                    "Could not fetch watermarks for topic: {}, partition: {}\n\nError: {}",
+                    topic,
+                    cursor.partition,
+                    err.to_string()
+                )
+            })?;
+
+        let end = cursor.offset.min(high);
+        let start = (end - page_size).max(low);
+        pages.push(PartitionPage {
+            partition: cursor.partition,
+            low,
+            start,
+            end,
+            messages: vec![],
+        });
+    }
+
+    read_pages(&consumer, topic, &mut pages, decoders).await?;
+
+    // Newest first, the messages of each partition are already sorted by offset
+    for page in pages.iter_mut() {
+        page.messages.reverse();
+    }
+
+    let (messages, taken) = merge_newest(&pages, page_size);
+    let cursors = get_cursors(&pages, &taken);
+
+    let has_more = pages
+        .iter()
+        .zip(cursors.iter())
+        .any(|(page, cursor)| cursor.offset > page.low);
+
+    Ok(MessagesPage {
+        messages,
+        cursors,
+        has_more,
+    })
+}
+
+struct PartitionPage {
+    partition: i32,
+    low: i64,
+    start: i64,
+    end: i64,
+    messages: Vec<KafkaMessageResponse>,
+}
+
+/// Read the pages of every partition with a single consumer, each partition assigned from the
+/// start of its page and paused once its end is reached
+async fn read_pages(
+    consumer: &StreamConsumer,
+    topic: &str,
+    pages: &mut [PartitionPage],
+    decoders: &TopicDecoders<'_>,
+) -> Result<(), String> {
+    let mut tpl = TopicPartitionList::new();
+    let mut unfinished = HashMap::new();
+    for (index, page) in pages.iter().enumerate() {
+        if page.end > page.start {
+            tpl.add_partition_offset(topic, page.partition, Offset::Offset(page.start))
+                .unwrap();
+            unfinished.insert(page.partition, index);
+        }
+    }
+    if unfinished.is_empty() {
+        return Ok(());
+    }
+    consumer.assign(&tpl).map_err(|err| {
+        format!(
+            "Could not assign topic partition for topic: {}\n\nError: {}",
+            topic,
+            err.to_string()
+        )
+    })?;
+
+    let timeout = Duration::from_secs(3);
+    while !unfinished.is_empty() {
+        let message = match tokio::time::timeout(timeout, consumer.recv()).await {
+            Ok(Ok(message)) => message,
+            Ok(Err(err)) => {
+                return Err(format!(
+                    "Could not read topic: {}\n\nError: {}",
+                    topic,
+                    err.to_string()
+                ))
+            }
+            Err(_) => {
+                // The last offsets of a page might never be delivered, e.g. transaction markers
+                let mut progressed = false;
+                for (partition, index) in unfinished.clone() {
+                    if get_position(consumer, topic, partition)? >= pages[index].end {
+                        unfinished.remove(&partition);
+                        progressed = true;
+                    }
+                }
+                // A short page would move the cursor past offsets that were never read
+                if !progressed {
+                    let mut partitions: Vec<&i32> = unfinished.keys().collect();
+                    partitions.sort();
+                    return Err(format!(
+                        "Timed out reading the page of topic: {}, partitions: {:?}",
+                        topic, partitions
+                    ));
+                }
+                continue;
+            }
+        };
+
+        let index = match unfinished.get(&message.partition()) {
+            Some(index) => *index,
+            None => continue,
+        };
+        let page = &mut pages[index];
+        if message.offset() < page.end {
+            let message_result = process_message(&message, decoders)
+                .await
+                .map_err(|err| {
+                    format!(
+                        "Could not process message for topic: {}, partition: {}, offset: {}\n\nError: {}",
+                        message.topic(),
+                        message.partition(),
+                        message.offset(),
+                        err
+                    )
+                })?;
+            page.messages.push(message_result);
+        }
+        if message.offset() + 1 >= page.end {
+            unfinished.remove(&page.partition);
+            pause_partition(consumer, topic, page.partition)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Newest messages of the pages, each sorted newest first, along with how many were taken from
+/// every page
+fn merge_newest(
+    pages: &[PartitionPage],
+    page_size: i64,
+) -> (Vec<KafkaMessageResponse>, Vec<usize>) {
+    let mut messages = vec![];
+    let mut taken = vec![0; pages.len()];
+    while (messages.len() as i64) < page_size {
+        let newest = pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| {
+                page.messages
+                    .get(taken[index])
+                    .map(|message| (index, message))
+            })
+            .max_by_key(|(_, message)| (message.timestamp, message.offset));
+        match newest {
+            Some((index, message)) => {
+                messages.push(message.clone());
+                taken[index] += 1;
+            }
+            None => break,
+        }
+    }
+
+    (messages, taken)
+}
+
+fn get_cursors(pages: &[PartitionPage], taken: &[usize]) -> Vec<PartitionCursor> {
+    pages
+        .iter()
+        .zip(taken)
+        .map(|(page, taken)| {
+            // Offsets without a message, e.g. transaction markers, are left behind along with
+            // the partition page when all its messages were taken
+            let offset = match *taken {
+                0 if !page.messages.is_empty() => page.end,
+                taken if taken == page.messages.len() => page.start,
+                taken => page.messages[taken - 1].offset,
+            };
+            PartitionCursor {
+                partition: page.partition,
+                offset,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(partition: i32, offset: i64, timestamp: i64) -> KafkaMessageResponse {
+        KafkaMessageResponse {
+            headers: None,
+            value: None,
+            key: None,
+            offset,
+            partition,
+            timestamp,
+            headers_encoding: None,
+            value_encoding: None,
+            key_encoding: None,
+        }
+    }
+
+    /// Messages given as (offset, timestamp), oldest first like they are read
+    fn page(partition: i32, start: i64, end: i64, messages: &[(i64, i64)]) -> PartitionPage {
+        PartitionPage {
+            partition,
+            low: 0,
+            start,
+            end,
+            messages: messages
+                .iter()
+                .rev()
+                .map(|(offset, timestamp)| message(partition, *offset, *timestamp))
+                .collect(),
+        }
+    }
+
+    fn positions(messages: &[KafkaMessageResponse]) -> Vec<(i32, i64)> {
+        messages
+            .iter()
+            .map(|message| (message.partition, message.offset))
+            .collect()
+    }
+
+    #[test]
+    fn merges_partitions_newest_first() {
+        let pages = [
+            page(0, 7, 10, &[(7, 100), (8, 300), (9, 500)]),
+            page(1, 2, 5, &[(2, 200), (3, 400), (4, 600)]),
+        ];
+
+        let (messages, taken) = merge_newest(&pages, 4);
+
+        assert_eq!(positions(&messages), vec![(1, 4), (0, 9), (1, 3), (0, 8)]);
+        assert_eq!(taken, vec![2, 2]);
+    }
+
+    #[test]
+    fn breaks_timestamp_ties_by_offset_and_keeps_partition_order() {
+        // Out of order timestamps inside a partition never reorder its offsets
+        let pages = [
+            page(0, 0, 3, &[(0, 100), (1, 900), (2, 100)]),
+            page(1, 5, 6, &[(5, 100)]),
+        ];
+
+        let (messages, _) = merge_newest(&pages, 4);
+
+        assert_eq!(positions(&messages), vec![(1, 5), (0, 2), (0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn continues_where_the_page_ends() {
+        let pages = [
+            page(0, 7, 10, &[(7, 100), (8, 300), (9, 500)]),
+            page(1, 2, 5, &[(2, 200), (3, 400), (4, 600)]),
+            page(2, 0, 4, &[(1, 50)]),
+            page(3, 3, 3, &[]),
+        ];
+
+        let (_, taken) = merge_newest(&pages, 4);
+        let cursors = get_cursors(&pages, &taken);
+
+        let offsets: Vec<(i32, i64)> = cursors
+            .iter()
+            .map(|cursor| (cursor.partition, cursor.offset))
+            .collect();
+        // Partially taken partitions restart before their oldest taken message, untouched ones
+        // from their end and exhausted ones from their start
+        assert_eq!(offsets, vec![(0, 8), (1, 3), (2, 4), (3, 3)]);
+
+        let (_, taken) = merge_newest(&pages, 10);
+        let offsets: Vec<i64> = get_cursors(&pages, &taken)
+            .iter()
+            .map(|cursor| cursor.offset)
+            .collect();
+        assert_eq!(offsets, vec![7, 2, 0, 3]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rdkafka::client::ClientContext;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::OwnedHeaders;
+use rdkafka::producer::{BaseRecord, DeliveryResult, ProducerContext, ThreadedProducer};
+use rdkafka::{ClientConfig, Message};
+use tokio::sync::oneshot;
+use tokio::time::Duration;
+
+use crate::connection::ProducerOptions;
+use crate::messages::DeliveryReport;
+
+/// Record serialized before being produced
+pub struct SerializedRecord {
+    pub headers: OwnedHeaders,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+    pub partition: Option<i32>,
+}
+
+type DeliverySender = oneshot::Sender<Result<DeliveryReport, String>>;
+
+struct ReportingContext;
+
+impl ClientContext for ReportingContext {}
+
+impl ProducerContext for ReportingContext {
+    type DeliveryOpaque = Box<DeliverySender>;
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, sender: Box<DeliverySender>) {
+        // The timestamp of the report is the one stored by the broker, the create time set by
+        // the producer or the log append time of the topic
+        let report = match delivery_result {
+            Ok(message) => Ok(DeliveryReport {
+                partition: message.partition(),
+                offset: message.offset(),
+                timestamp: message.timestamp().to_millis(),
+            }),
+            Err((err, _)) => Err(format!(
+                "Error while sending the message: {}",
+                err.to_string()
+            )),
+        };
+        let _ = sender.send(report);
+    }
+}
+
+/// Producer of single messages reporting the timestamp of every delivery
+#[derive(Clone)]
+pub struct ReportingProducer {
+    producer: Arc<ThreadedProducer<ReportingContext>>,
+}
+
+impl ReportingProducer {
+    pub fn new(producer_config: &ClientConfig) -> Result<ReportingProducer, String> {
+        let producer = producer_config
+            .create_with_context(ReportingContext)
+            .map_err(|err| format!("Error creating producer connection: {}", err.to_string()))?;
+
+        Ok(ReportingProducer {
+            producer: Arc::new(producer),
+        })
+    }
+
+    pub async fn send(
+        &self,
+        topic: &str,
+        record: &SerializedRecord,
+    ) -> Result<DeliveryReport, String> {
+        let (sender, receiver) = oneshot::channel();
+        let mut base_record: BaseRecord<'_, [u8], [u8], Box<DeliverySender>> =
+            BaseRecord::with_opaque_to(topic, Box::new(sender)).headers(record.headers.clone());
+        if let Some(ref key) = record.key {
+            base_record = base_record.key(key.as_slice());
+        }
+        if let Some(ref value) = record.value {
+            base_record = base_record.payload(value.as_slice());
+        }
+        if let Some(partition) = record.partition {
+            base_record = base_record.partition(partition);
+        }
+
+        loop {
+            match self.producer.send(base_record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    base_record = returned;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err((err, _)) => {
+                    return Err(format!(
+                        "Error while sending the message: {}",
+                        err.to_string()
+                    ))
+                }
+            }
+        }
+
+        receiver
+            .await
+            .map_err(|_| "The message delivery was cancelled".to_owned())?
+    }
+}
+
+/// Producers of the single messages of a connection, created on first use for every producer
+/// options and kept until the connection changes
+pub struct ReportingProducers {
+    producer_config: ClientConfig,
+    producers: Mutex<HashMap<ProducerOptions, ReportingProducer>>,
+}
+
+impl ReportingProducers {
+    pub fn new(producer_config: ClientConfig) -> ReportingProducers {
+        ReportingProducers {
+            producer_config,
+            producers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Producer of the connection with the options applied on top of its own
+    pub fn get(&self, options: Option<ProducerOptions>) -> Result<ReportingProducer, String> {
+        let options = options.unwrap_or_default();
+        let mut producers = self.producers.lock().unwrap();
+        if let Some(producer) = producers.get(&options) {
+            return Ok(producer.clone());
+        }
+
+        let mut producer_config = self.producer_config.clone();
+        options.apply(&mut producer_config);
+        let producer = ReportingProducer::new(&producer_config)?;
+        producers.insert(options, producer.clone());
+
+        Ok(producer)
+    }
+}
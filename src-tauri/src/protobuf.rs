@@ -0,0 +1,411 @@
+use std::fs;
+
+use prost::encoding::{decode_varint, encode_varint};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::schema_registry::split_confluent_frame;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProtobufDescriptorConfig {
+    /// Compiled FileDescriptorSet, e.g. from `protoc --include_imports --descriptor_set_out`
+    pub path: String,
+    /// Topic the messages apply to, every topic of the connection when not set
+    pub topic: Option<String>,
+    pub key_message: Option<String>,
+    pub value_message: Option<String>,
+}
+
+struct ProtobufBinding {
+    topic: Option<String>,
+    pool: DescriptorPool,
+    key_message: Option<MessageDescriptor>,
+    value_message: Option<MessageDescriptor>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MessagePart {
+    Key,
+    Value,
+    Headers,
+}
+
+pub struct ProtobufRegistry {
+    bindings: Vec<ProtobufBinding>,
+}
+
+impl ProtobufRegistry {
+    pub fn new(configs: Vec<ProtobufDescriptorConfig>) -> Result<ProtobufRegistry, String> {
+        let mut bindings = vec![];
+        for config in configs {
+            let bytes = fs::read(&config.path).map_err(|err| {
+                format!(
+                    "Could not read descriptor set {}: {}",
+                    config.path,
+                    err.to_string()
+                )
+            })?;
+            let pool = DescriptorPool::decode(bytes.as_slice()).map_err(|err| {
+                format!(
+                    "Invalid descriptor set {}: {}",
+                    config.path,
+                    err.to_string()
+                )
+            })?;
+
+            let key_message = get_message_from_pool(&pool, &config.path, config.key_message)?;
+            let value_message = get_message_from_pool(&pool, &config.path, config.value_message)?;
+
+            bindings.push(ProtobufBinding {
+                topic: config.topic,
+                pool,
+                key_message,
+                value_message,
+            });
+        }
+
+        Ok(ProtobufRegistry { bindings })
+    }
+
+    /// Message type bound to the topic, topic bindings take precedence over the connection wide ones
+    pub fn get_topic_message(&self, topic: &str, part: MessagePart) -> Option<&MessageDescriptor> {
+        let topic_binding = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.topic.as_deref() == Some(topic))
+            .find_map(|binding| binding.get_message(part));
+
+        topic_binding.or_else(|| {
+            self.bindings
+                .iter()
+                .filter(|binding| binding.topic.is_none())
+                .find_map(|binding| binding.get_message(part))
+        })
+    }
+
+    /// Look for a message type by its full name in every registered descriptor set
+    pub fn get_message(&self, name: &str) -> Option<MessageDescriptor> {
+        self.bindings
+            .iter()
+            .find_map(|binding| binding.pool.get_message_by_name(name))
+    }
+}
+
+impl ProtobufBinding {
+    fn get_message(&self, part: MessagePart) -> Option<&MessageDescriptor> {
+        match part {
+            MessagePart::Key => self.key_message.as_ref(),
+            MessagePart::Value => self.value_message.as_ref(),
+            MessagePart::Headers => None,
+        }
+    }
+}
+
+fn get_message_from_pool(
+    pool: &DescriptorPool,
+    path: &str,
+    name: Option<String>,
+) -> Result<Option<MessageDescriptor>, String> {
+    match name {
+        Some(name) => match pool.get_message_by_name(&name) {
+            Some(message) => Ok(Some(message)),
+            None => Err(format!(
+                "Message {} not found in descriptor set {}",
+                name, path
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Decode a protobuf payload into JSON, with or without the Confluent framing
+pub fn decode_protobuf(descriptor: &MessageDescriptor, bytes: &[u8]) -> Result<Value, String> {
+    let (descriptor, body) = match split_confluent_frame(bytes) {
+        Some((_, mut framed_body)) => {
+            let indexes = read_message_indexes(&mut framed_body)?;
+            (resolve_message_indexes(descriptor, &indexes)?, framed_body)
+        }
+        None => (descriptor.clone(), bytes),
+    };
+
+    let message = DynamicMessage::decode(descriptor.clone(), body).map_err(|err| {
+        format!(
+            "Could not decode protobuf message {}: {}",
+            descriptor.full_name(),
+            err.to_string()
+        )
+    })?;
+
+    serde_json::to_value(&message).map_err(|err| {
+        format!(
+            "Could not convert protobuf message {} to JSON: {}",
+            descriptor.full_name(),
+            err.to_string()
+        )
+    })
+}
+
+/// Encode a JSON document as protobuf, framed with the Confluent header when a schema id is given
+pub fn encode_protobuf(
+    descriptor: &MessageDescriptor,
+    json: &str,
+    schema_id: Option<u32>,
+) -> Result<Vec<u8>, String> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let message = DynamicMessage::deserialize(descriptor.clone(), &mut deserializer)
+        .and_then(|message| deserializer.end().map(|_| message))
+        .map_err(|err| {
+            format!(
+                "Message does not match the protobuf message {}: {}",
+                descriptor.full_name(),
+                err.to_string()
+            )
+        })?;
+
+    let mut bytes = vec![];
+    if let Some(schema_id) = schema_id {
+        bytes.push(0);
+        bytes.extend_from_slice(&schema_id.to_be_bytes());
+        write_message_indexes(&get_message_indexes(descriptor), &mut bytes);
+    }
+    bytes.extend(message.encode_to_vec());
+
+    Ok(bytes)
+}
+
+// https://docs.confluent.io/platform/current/schema-registry/fundamentals/serdes-develop/index.html#wire-format
+// The message indexes are zigzag varints, a single 0 stands for the first message of the file
+fn read_message_indexes(body: &mut &[u8]) -> Result<Vec<i64>, String> {
+    let length = read_zigzag(body)?;
+    if length == 0 {
+        return Ok(vec![0]);
+    }
+    if length < 0 {
+        return Err(format!(
+            "Invalid protobuf message indexes: negative length {}",
+            length
+        ));
+    }
+
+    let mut indexes = vec![];
+    for _ in 0..length {
+        let index = read_zigzag(body)?;
+        if index < 0 {
+            return Err(format!(
+                "Invalid protobuf message indexes: negative index {}",
+                index
+            ));
+        }
+        indexes.push(index);
+    }
+
+    Ok(indexes)
+}
+
+fn write_message_indexes(indexes: &[i64], bytes: &mut Vec<u8>) {
+    if indexes == [0] {
+        bytes.push(0);
+        return;
+    }
+
+    write_zigzag(indexes.len() as i64, bytes);
+    for index in indexes {
+        write_zigzag(*index, bytes);
+    }
+}
+
+fn read_zigzag(body: &mut &[u8]) -> Result<i64, String> {
+    let value = decode_varint(body)
+        .map_err(|err| format!("Invalid protobuf message indexes: {}", err.to_string()))?;
+    Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+}
+
+fn write_zigzag(value: i64, bytes: &mut Vec<u8>) {
+    encode_varint(((value << 1) ^ (value >> 63)) as u64, bytes);
+}
+
+/// The indexes point to a message of the file the configured message is defined in
+fn resolve_message_indexes(
+    descriptor: &MessageDescriptor,
+    indexes: &[i64],
+) -> Result<MessageDescriptor, String> {
+    let file = descriptor.parent_file();
+    let invalid_indexes = || format!("Message indexes {:?} not found in {}", indexes, file.name());
+
+    let (first, children) = indexes.split_first().ok_or_else(invalid_indexes)?;
+    let mut message = file
+        .messages()
+        .nth(*first as usize)
+        .ok_or_else(invalid_indexes)?;
+    for index in children {
+        let child = message
+            .child_messages()
+            .nth(*index as usize)
+            .ok_or_else(invalid_indexes)?;
+        message = child;
+    }
+
+    Ok(message)
+}
+
+fn get_message_indexes(descriptor: &MessageDescriptor) -> Vec<i64> {
+    let mut indexes = vec![];
+    let mut message = descriptor.clone();
+    loop {
+        let parent = message.parent_message();
+        let index = match parent {
+            Some(ref parent) => parent
+                .child_messages()
+                .position(|child| child.full_name() == message.full_name()),
+            None => message
+                .parent_file()
+                .messages()
+                .position(|sibling| sibling.full_name() == message.full_name()),
+        };
+        indexes.push(index.unwrap_or(0) as i64);
+
+        match parent {
+            Some(parent) => message = parent,
+            None => break,
+        }
+    }
+
+    indexes.reverse();
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_reflect::prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn field(name: &str, number: i32, field_type: Type) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            json_name: Some(name.to_owned()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(field_type as i32),
+            ..Default::default()
+        }
+    }
+
+    // message Outer { string name = 1; message Inner { int32 id = 1; } }
+    // message Other { int32 count = 1; }
+    fn create_pool() -> DescriptorPool {
+        let inner = DescriptorProto {
+            name: Some("Inner".to_owned()),
+            field: vec![field("id", 1, Type::Int32)],
+            ..Default::default()
+        };
+        let outer = DescriptorProto {
+            name: Some("Outer".to_owned()),
+            field: vec![field("name", 1, Type::String)],
+            nested_type: vec![inner],
+            ..Default::default()
+        };
+        let other = DescriptorProto {
+            name: Some("Other".to_owned()),
+            field: vec![field("count", 1, Type::Int32)],
+            ..Default::default()
+        };
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(FileDescriptorProto {
+            name: Some("test.proto".to_owned()),
+            package: Some("test".to_owned()),
+            message_type: vec![outer, other],
+            syntax: Some("proto3".to_owned()),
+            ..Default::default()
+        })
+        .unwrap();
+        pool
+    }
+
+    #[test]
+    fn writes_message_indexes_as_zigzag_varints() {
+        let cases: [(&[i64], &[u8]); 4] = [
+            (&[0], &[0]),
+            (&[1], &[2, 2]),
+            (&[0, 0], &[4, 0, 0]),
+            (&[70], &[2, 140, 1]),
+        ];
+
+        for (indexes, expected) in cases {
+            let mut bytes = vec![];
+            write_message_indexes(indexes, &mut bytes);
+            assert_eq!(bytes, expected);
+
+            let mut body = bytes.as_slice();
+            assert_eq!(read_message_indexes(&mut body).unwrap(), indexes);
+            assert!(body.is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_message_indexes() {
+        let mut body: &[u8] = &[0x80];
+        assert!(read_message_indexes(&mut body).is_err());
+
+        // Zigzag 0x01 is -1, which would otherwise read as no index at all
+        let mut body: &[u8] = &[0x01];
+        assert!(read_message_indexes(&mut body).is_err());
+        let mut body: &[u8] = &[0x02, 0x01];
+        assert!(read_message_indexes(&mut body).is_err());
+    }
+
+    #[test]
+    fn locates_nested_messages() {
+        let pool = create_pool();
+        let inner = pool.get_message_by_name("test.Outer.Inner").unwrap();
+        let other = pool.get_message_by_name("test.Other").unwrap();
+
+        assert_eq!(get_message_indexes(&inner), vec![0, 0]);
+        assert_eq!(get_message_indexes(&other), vec![1]);
+        assert_eq!(
+            resolve_message_indexes(&other, &[0, 0])
+                .unwrap()
+                .full_name(),
+            "test.Outer.Inner"
+        );
+        assert!(resolve_message_indexes(&other, &[2]).is_err());
+    }
+
+    #[test]
+    fn round_trips_framed_and_plain_payloads() {
+        let pool = create_pool();
+        let inner = pool.get_message_by_name("test.Outer.Inner").unwrap();
+        let outer = pool.get_message_by_name("test.Outer").unwrap();
+
+        let framed = encode_protobuf(&inner, r#"{"id": 150}"#, Some(7)).unwrap();
+        assert_eq!(framed, vec![0, 0, 0, 0, 7, 4, 0, 0, 8, 150, 1]);
+        // The message indexes win over the message configured for the topic
+        assert_eq!(
+            decode_protobuf(&outer, &framed).unwrap(),
+            json!({"id": 150})
+        );
+
+        let plain = encode_protobuf(&outer, r#"{"name": "Ada"}"#, None).unwrap();
+        assert_eq!(plain, vec![10, 3, b'A', b'd', b'a']);
+        assert_eq!(
+            decode_protobuf(&outer, &plain).unwrap(),
+            json!({"name": "Ada"})
+        );
+    }
+
+    #[test]
+    fn rejects_json_not_matching_the_message() {
+        let pool = create_pool();
+        let other = pool.get_message_by_name("test.Other").unwrap();
+
+        assert!(encode_protobuf(&other, r#"{"unknown": 1}"#, None).is_err());
+        assert!(encode_protobuf(&other, r#"{"count": 1} trailing"#, None).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::ffi::CStr;
+use std::os::raw::c_int;
+use std::slice;
+
+use rdkafka::admin::AdminClient;
+use rdkafka::bindings;
+use rdkafka::client::DefaultClientContext;
+use rdkafka::types::{RDKafkaErrorCode, RDKafkaRespErr};
+use rdkafka::{Offset, TopicPartitionList};
+use serde::Serialize;
+use tokio::time::Duration;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DeletedRecords {
+    pub partition: i32,
+    /// New low watermark of the partition
+    pub low_watermark: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Delete the records of the partitions before the offsets of the list, `Offset::End` deletes up
+/// to the high watermark. rdkafka does not wrap the DeleteRecords admin API, so it is called
+/// directly on the librdkafka handle of the admin client
+pub async fn delete_records(
+    admin: &AdminClient<DefaultClientContext>,
+    topic: &str,
+    before_offsets: &TopicPartitionList,
+    timeout: Duration,
+) -> Result<Vec<DeletedRecords>, String> {
+    let client = admin.inner().native_ptr();
+    let timeout_ms = timeout.as_millis() as c_int;
+
+    // SAFETY: the client handle is owned by the admin client borrowed for the whole call, the
+    // options and the request are created from it and destroyed before leaving the block. The
+    // request only reads the partition list, which stays borrowed
+    let queue = unsafe {
+        let queue = EventQueue(bindings::rd_kafka_queue_new(client));
+        let options = bindings::rd_kafka_AdminOptions_new(
+            client,
+            bindings::rd_kafka_admin_op_t::RD_KAFKA_ADMIN_OP_DELETERECORDS,
+        );
+        let mut errstr = [0; 512];
+        let err = bindings::rd_kafka_AdminOptions_set_operation_timeout(
+            options,
+            timeout_ms,
+            errstr.as_mut_ptr(),
+            errstr.len(),
+        );
+        if err != RDKafkaRespErr::RD_KAFKA_RESP_ERR_NO_ERROR {
+            bindings::rd_kafka_AdminOptions_destroy(options);
+            return Err(format!(
+                "Invalid timeout deleting records of topic: {}\n\nError: {}",
+                topic,
+                CStr::from_ptr(errstr.as_ptr()).to_string_lossy()
+            ));
+        }
+
+        let mut del_records = bindings::rd_kafka_DeleteRecords_new(before_offsets.ptr());
+        bindings::rd_kafka_DeleteRecords(client, &mut del_records, 1, options, queue.0);
+        bindings::rd_kafka_DeleteRecords_destroy(del_records);
+        bindings::rd_kafka_AdminOptions_destroy(options);
+        queue
+    };
+
+    // The broker waits for the operation timeout at most, the request timeout on top of it.
+    // Polling blocks the thread until then, so it runs outside of the async runtime
+    let topic = topic.to_owned();
+    tokio::task::spawn_blocking(move || {
+        // SAFETY: the queue is only used by this task and destroyed when it is dropped, the
+        // event is read before being destroyed and nothing borrowed from it is returned
+        unsafe {
+            let event = queue.poll(timeout_ms * 2);
+            if event.is_null() {
+                return Err(format!("Timed out deleting records of topic: {}", topic));
+            }
+            let result = read_delete_records_result(event, &topic);
+            bindings::rd_kafka_event_destroy(event);
+            result
+        }
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+/// Queue receiving the result of the request
+struct EventQueue(*mut bindings::rd_kafka_queue_t);
+
+impl EventQueue {
+    fn poll(&self, timeout_ms: c_int) -> *mut bindings::rd_kafka_event_t {
+        // SAFETY: the queue stays valid until it is dropped
+        unsafe { bindings::rd_kafka_queue_poll(self.0, timeout_ms) }
+    }
+}
+
+// SAFETY: librdkafka queues can be used from any thread, the pointer is owned by a single
+// EventQueue and never shared
+unsafe impl Send for EventQueue {}
+
+impl Drop for EventQueue {
+    fn drop(&mut self) {
+        // SAFETY: the queue was created by rd_kafka_queue_new and is destroyed only once
+        unsafe { bindings::rd_kafka_queue_destroy(self.0) }
+    }
+}
+
+/// # Safety
+///
+/// `event` must be a valid, not yet destroyed, event returned by polling a DeleteRecords request
+unsafe fn read_delete_records_result(
+    event: *mut bindings::rd_kafka_event_t,
+    topic: &str,
+) -> Result<Vec<DeletedRecords>, String> {
+    let err = bindings::rd_kafka_event_error(event);
+    if err != RDKafkaRespErr::RD_KAFKA_RESP_ERR_NO_ERROR {
+        let error_string = bindings::rd_kafka_event_error_string(event);
+        let error_string = if error_string.is_null() {
+            RDKafkaErrorCode::from(err).to_string()
+        } else {
+            // SAFETY: a non null error string is a C string owned by the event
+            CStr::from_ptr(error_string).to_string_lossy().into_owned()
+        };
+        return Err(format!(
+            "Error deleting records of topic: {}\n\nError: {}",
+            topic, error_string
+        ));
+    }
+
+    let result = bindings::rd_kafka_event_DeleteRecords_result(event);
+    if result.is_null() {
+        return Err(format!(
+            "Unexpected response deleting records of topic: {}",
+            topic
+        ));
+    }
+
+    // SAFETY: the offsets belong to the event, they are copied into owned results before it is
+    // destroyed. A non empty list points to `cnt` contiguous elements
+    let offsets = &*bindings::rd_kafka_DeleteRecords_result_offsets(result);
+    let elements = if offsets.cnt > 0 {
+        slice::from_raw_parts(offsets.elems, offsets.cnt as usize)
+    } else {
+        &[]
+    };
+
+    Ok(elements
+        .iter()
+        .map(|element| {
+            if element.err == RDKafkaRespErr::RD_KAFKA_RESP_ERR_NO_ERROR {
+                DeletedRecords {
+                    partition: element.partition,
+                    low_watermark: match Offset::from_raw(element.offset) {
+                        Offset::Offset(offset) => Some(offset),
+                        _ => None,
+                    },
+                    error: None,
+                }
+            } else {
+                DeletedRecords {
+                    partition: element.partition,
+                    low_watermark: None,
+                    error: Some(RDKafkaErrorCode::from(element.err).to_string()),
+                }
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Headers;
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::decoders::TopicDecoders;
+use crate::encoding::PayloadEncoding;
+use crate::messages::{
+    process_message, send_message, DeliveryReport, KafkaMessageResponse, MessageCodecs,
+    OutgoingMessage,
+};
+use crate::producers::ReportingProducer;
+use crate::search::create_reader_consumer;
+
+const DEFAULT_CORRELATION_HEADER: &str = "correlation-id";
+
+/// Where the reply is expected and how long to wait for it
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReplyOptions {
+    pub reply_topic: String,
+    /// `correlation-id` when not set
+    pub correlation_header: Option<String>,
+    pub timeout_ms: u64,
+    /// Used for the fields of the reply no decoder applies to
+    pub encoding: Option<PayloadEncoding>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RoundTrip {
+    pub correlation_id: String,
+    pub request: DeliveryReport,
+    /// First record of the reply topic with the correlation id, none when timed out
+    pub reply: Option<KafkaMessageResponse>,
+    /// Milliseconds between sending the request and receiving the reply
+    pub latency: Option<u64>,
+}
+
+/// Send the request with a generated correlation id, added to its headers, and wait for the
+/// record of the reply topic carrying the same id in its correlation header
+pub async fn request_reply(
+    common_config: &ClientConfig,
+    producer: &ReportingProducer,
+    mut request: OutgoingMessage,
+    options: ReplyOptions,
+    decoders: &TopicDecoders<'_>,
+    codecs: MessageCodecs<'_>,
+) -> Result<RoundTrip, String> {
+    let reply_topic = options.reply_topic;
+    let correlation_header = options
+        .correlation_header
+        .unwrap_or_else(|| DEFAULT_CORRELATION_HEADER.to_owned());
+    let correlation_id = Uuid::new_v4().to_string();
+
+    // The reply topic is read from its current end before sending, so fast replies are not missed
+    let consumer = create_reader_consumer(common_config)?;
+    assign_from_end(&consumer, &reply_topic)?;
+
+    request
+        .headers
+        .get_or_insert_with(HashMap::new)
+        .insert(correlation_header.clone(), Some(correlation_id.clone()));
+
+    let started_at = Instant::now();
+    let delivery = send_message(producer, request, codecs).await?;
+
+    let deadline = started_at + Duration::from_millis(options.timeout_ms);
+    loop {
+        let message = match tokio::time::timeout_at(deadline, consumer.recv()).await {
+            Ok(Ok(message)) => message,
+            Ok(Err(err)) => {
+                return Err(format!(
+                    "Error while waiting for the reply on topic: {}\n\nError: {}",
+                    reply_topic,
+                    err.to_string()
+                ))
+            }
+            Err(_) => {
+                return Ok(RoundTrip {
+                    correlation_id,
+                    request: delivery,
+                    reply: None,
+                    latency: None,
+                })
+            }
+        };
+        let received_at = Instant::now();
+
+        // Header values are compared as raw bytes, whatever the decoders of the topic
+        let is_reply = message.headers().map_or(false, |headers| {
+            headers.iter().any(|header| {
+                header.key == correlation_header && header.value == Some(correlation_id.as_bytes())
+            })
+        });
+        if !is_reply {
+            continue;
+        }
+
+        let reply = process_message(&message, decoders).await.map_err(|err| {
+            format!(
+                "Could not process reply for topic: {}, partition: {}, offset: {}\n\nError: {}",
+                message.topic(),
+                message.partition(),
+                message.offset(),
+                err
+            )
+        })?;
+
+        return Ok(RoundTrip {
+            correlation_id,
+            request: delivery,
+            reply: Some(reply),
+            latency: Some((received_at - started_at).as_millis() as u64),
+        });
+    }
+}
+
+/// Assign every partition of the topic at its high watermark
+fn assign_from_end(consumer: &StreamConsumer, topic: &str) -> Result<(), String> {
+    let metadata = consumer
+        .fetch_metadata(Some(topic), Duration::from_secs(30))
+        .map_err(|err| {
+            format!(
+                "Could not fetch topic metadada for topic: {}\n\nError: {}",
+                topic,
+                err.to_string()
+            )
+        })?;
+    let partitions = match metadata.topics().first() {
+        Some(metadata_topic) if metadata_topic.error().is_none() => metadata_topic.partitions(),
+        _ => return Err(format!("Topic not found: {}", topic)),
+    };
+
+    // Offset::End would only be resolved on the first fetch, after the request might be answered
+    let mut tpl = TopicPartitionList::new();
+    for partition in partitions {
+        let (_, high) = consumer
+            .fetch_watermarks(topic, partition.id(), Duration::from_secs(30))
+            .map_err(|err| {
+                format!(
+                    "Could not fetch watermarks for topic: {}, partition: {}\n\nError: {}",
+                    topic,
+                    partition.id(),
+                    err.to_string()
+                )
+            })?;
+        tpl.add_partition_offset(topic, partition.id(), Offset::Offset(high))
+            .map_err(|err| err.to_string())?;
+    }
+
+    consumer.assign(&tpl).map_err(|err| {
+        format!(
+            "Could not assign topic partition for topic: {}\n\nError: {}",
+            topic,
+            err.to_string()
+        )
+    })
+}
@@ -0,0 +1,537 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use apache_avro::{
+    from_avro_datum_schemata, to_avro_datum_schemata, types::Value as AvroValue, Schema,
+};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+// https://docs.confluent.io/platform/current/schema-registry/fundamentals/serdes-develop/index.html#wire-format
+const CONFLUENT_MAGIC_BYTE: u8 = 0;
+const CONFLUENT_HEADER_LENGTH: usize = 5;
+
+// Failed lookups are not retried before this delay, so a bad id does not stall every record
+const FAILURE_TTL: Duration = Duration::from_secs(60);
+
+// A new version registered under the subject is picked up after this delay
+const LATEST_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SchemaRegistryConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SchemaResponse {
+    // Only returned when fetching by subject
+    id: Option<u32>,
+    schema: String,
+    schema_type: Option<String>,
+    #[serde(default)]
+    references: Vec<SchemaReferenceResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SchemaReferenceResponse {
+    subject: String,
+    version: i32,
+}
+
+/// Avro schema registered under an id, along with the schemas it references
+pub struct RegistrySchema {
+    pub schema: Schema,
+    pub references: Vec<Schema>,
+}
+
+impl RegistrySchema {
+    /// All the schemas needed to resolve named types, including the schema itself
+    pub fn schemata(&self) -> Vec<&Schema> {
+        std::iter::once(&self.schema)
+            .chain(self.references.iter())
+            .collect()
+    }
+}
+
+type SubjectVersion = (String, Option<i32>);
+
+pub struct SchemaRegistryClient {
+    config: SchemaRegistryConfig,
+    http: reqwest::Client,
+    schemas: RwLock<HashMap<u32, Arc<RegistrySchema>>>,
+    failures: RwLock<HashMap<u32, (Instant, String)>>,
+    /// Schema ids of the subject versions, the latest version when none is given
+    subjects: RwLock<HashMap<SubjectVersion, (Instant, u32)>>,
+}
+
+impl SchemaRegistryClient {
+    pub fn new(config: SchemaRegistryConfig) -> Result<SchemaRegistryClient, String> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|err| format!("Error creating schema registry client: {}", err.to_string()))?;
+
+        Ok(SchemaRegistryClient {
+            config,
+            http,
+            schemas: RwLock::new(HashMap::new()),
+            failures: RwLock::new(HashMap::new()),
+            subjects: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Get the schema registered with the given id, schemas are immutable so they are cached
+    /// forever. Failures (unknown id, unsupported type, registry down) are cached for a while
+    pub async fn get_schema(&self, id: u32) -> Result<Arc<RegistrySchema>, String> {
+        if let Some(schema) = self.schemas.read().await.get(&id) {
+            return Ok(schema.clone());
+        }
+        if let Some((failed_at, error)) = self.failures.read().await.get(&id) {
+            if failed_at.elapsed() < FAILURE_TTL {
+                return Err(error.clone());
+            }
+        }
+
+        match self.fetch_schema(id).await {
+            Ok(schema) => {
+                self.failures.write().await.remove(&id);
+                self.schemas.write().await.insert(id, schema.clone());
+                Ok(schema)
+            }
+            Err(err) => {
+                self.failures
+                    .write()
+                    .await
+                    .insert(id, (Instant::now(), err.clone()));
+                Err(err)
+            }
+        }
+    }
+
+    async fn fetch_schema(&self, id: u32) -> Result<Arc<RegistrySchema>, String> {
+        let response: SchemaResponse = self.get(&format!("schemas/ids/{}", id)).await?;
+        Ok(Arc::new(self.parse_schema(response).await?))
+    }
+
+    /// Get the schema registered under a subject, the latest version when none is given.
+    /// Versions are immutable so they are cached forever, the latest one only for a while
+    pub async fn get_subject_schema(
+        &self,
+        subject: &str,
+        version: Option<i32>,
+    ) -> Result<(u32, Arc<RegistrySchema>), String> {
+        let subject_key = (subject.to_owned(), version);
+        let cached_id = match self.subjects.read().await.get(&subject_key) {
+            Some((fetched_at, id)) if version.is_some() || fetched_at.elapsed() < LATEST_TTL => {
+                Some(*id)
+            }
+            _ => None,
+        };
+        if let Some(id) = cached_id {
+            if let Some(schema) = self.schemas.read().await.get(&id) {
+                return Ok((id, schema.clone()));
+            }
+        }
+
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => "latest".to_owned(),
+        };
+
+        let response: SchemaResponse = self
+            .get(&format!(
+                "subjects/{}/versions/{}",
+                encode_path_segment(subject),
+                version
+            ))
+            .await?;
+        let id = response.id.ok_or(format!(
+            "Schema registry returned no schema id for subject {} version {}",
+            subject, version
+        ))?;
+
+        let cached_schema = self.schemas.read().await.get(&id).cloned();
+        let schema = match cached_schema {
+            Some(schema) => schema,
+            None => {
+                let schema = Arc::new(self.parse_schema(response).await?);
+                self.schemas.write().await.insert(id, schema.clone());
+                schema
+            }
+        };
+        self.subjects
+            .write()
+            .await
+            .insert(subject_key, (Instant::now(), id));
+
+        Ok((id, schema))
+    }
+
+    async fn parse_schema(&self, response: SchemaResponse) -> Result<RegistrySchema, String> {
+        let schema_type = response.schema_type.as_deref().unwrap_or("AVRO");
+        if schema_type != "AVRO" {
+            return Err(format!("Unsupported schema type {}", schema_type));
+        }
+
+        // References have to be parsed before the schema that uses them
+        let mut definitions = vec![];
+        self.get_references(response.references, &mut definitions)
+            .await?;
+        definitions.push(response.schema);
+
+        let definitions: Vec<&str> = definitions.iter().map(|x| x.as_str()).collect();
+        let mut schemas = Schema::parse_list(&definitions)
+            .map_err(|err| format!("Could not parse avro schema: {}", err.to_string()))?;
+        let schema = schemas.pop().unwrap();
+
+        Ok(RegistrySchema {
+            schema,
+            references: schemas,
+        })
+    }
+
+    /// Collect the definitions of the referenced schemas, nested references first
+    fn get_references<'a>(
+        &'a self,
+        references: Vec<SchemaReferenceResponse>,
+        definitions: &'a mut Vec<String>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            for reference in references {
+                let response: SchemaResponse = self
+                    .get(&format!(
+                        "subjects/{}/versions/{}",
+                        encode_path_segment(&reference.subject),
+                        reference.version
+                    ))
+                    .await?;
+
+                self.get_references(response.references, definitions)
+                    .await?;
+
+                if !definitions.contains(&response.schema) {
+                    definitions.push(response.schema);
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let url = format!("{}/{}", self.config.url.trim_end_matches('/'), path);
+
+        let mut request = self
+            .http
+            .get(&url)
+            .header("Accept", "application/vnd.schemaregistry.v1+json");
+        if let Some(ref username) = self.config.username {
+            request = request.basic_auth(username, self.config.password.as_ref());
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|err| format!("Error calling schema registry {}: {}", url, err.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "Schema registry responded {} for {}: {}",
+                status, url, body
+            ));
+        }
+
+        response.json::<T>().await.map_err(|err| {
+            format!(
+                "Unexpected schema registry response for {}: {}",
+                url,
+                err.to_string()
+            )
+        })
+    }
+}
+
+/// Percent-encode everything but the unreserved characters, subjects can contain `/` or `?`
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Split a payload in the Confluent wire format into schema id and body
+pub fn split_confluent_frame(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    if bytes.len() < CONFLUENT_HEADER_LENGTH || bytes[0] != CONFLUENT_MAGIC_BYTE {
+        return None;
+    }
+
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&bytes[1..CONFLUENT_HEADER_LENGTH]);
+
+    Some((u32::from_be_bytes(id), &bytes[CONFLUENT_HEADER_LENGTH..]))
+}
+
+/// Decode an Avro payload framed with its registry schema id into JSON
+pub async fn decode_avro(
+    registry: &SchemaRegistryClient,
+    bytes: &[u8],
+) -> Result<Option<Value>, String> {
+    let (schema_id, mut body) = match split_confluent_frame(bytes) {
+        Some(frame) => frame,
+        None => return Ok(None),
+    };
+
+    let registry_schema = registry.get_schema(schema_id).await?;
+    let value = from_avro_datum_schemata(
+        &registry_schema.schema,
+        registry_schema.schemata(),
+        &mut body,
+        None,
+    )
+    .map_err(|err| {
+        format!(
+            "Could not decode avro payload with schema id {}: {}",
+            schema_id,
+            err.to_string()
+        )
+    })?;
+
+    let json = Value::try_from(value).map_err(|err| {
+        format!(
+            "Could not convert avro payload with schema id {} to JSON: {}",
+            schema_id,
+            err.to_string()
+        )
+    })?;
+
+    Ok(Some(json))
+}
+
+/// Encode a JSON document with the schema of the given subject, framed with the schema id
+pub async fn encode_avro(
+    registry: &SchemaRegistryClient,
+    subject: &str,
+    version: Option<i32>,
+    json: Value,
+) -> Result<Vec<u8>, String> {
+    let (schema_id, registry_schema) = registry.get_subject_schema(subject, version).await?;
+
+    // Resolving coerces the plain JSON types (maps, numbers, ...) into the ones the schema expects
+    let value = AvroValue::from(json)
+        .resolve_schemata(&registry_schema.schema, registry_schema.schemata())
+        .map_err(|err| {
+            format!(
+                "Message does not match the avro schema of subject {}: {}",
+                subject,
+                err.to_string()
+            )
+        })?;
+
+    let body = to_avro_datum_schemata(&registry_schema.schema, registry_schema.schemata(), value)
+        .map_err(|err| {
+        format!(
+            "Could not encode message with the avro schema of subject {}: {}",
+            subject,
+            err.to_string()
+        )
+    })?;
+
+    let mut bytes = Vec::with_capacity(CONFLUENT_HEADER_LENGTH + body.len());
+    bytes.push(CONFLUENT_MAGIC_BYTE);
+    bytes.extend_from_slice(&schema_id.to_be_bytes());
+    bytes.extend(body);
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use serde_json::json;
+
+    use super::*;
+
+    const USER_SCHEMA: &str =
+        r#"{"type":"record","name":"User","fields":[{"name":"name","type":"string"}]}"#;
+
+    /// Request line and authorization header of every request received by the stub
+    type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+    /// Registry answering id 1 with the user schema, also registered as the latest version of
+    /// subject `users/v1-value`, id 2 with a protobuf schema and 404 otherwise
+    fn start_stub_registry() -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Requests = Arc::new(Mutex::new(vec![]));
+
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut authorization = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("authorization") {
+                            authorization = Some(value.trim().to_owned());
+                        }
+                    }
+                }
+
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_owned();
+                received
+                    .lock()
+                    .unwrap()
+                    .push((request_line.trim().to_owned(), authorization));
+
+                let (status, body) = match path.as_str() {
+                    "/schemas/ids/1" => ("200 OK", json!({ "schema": USER_SCHEMA })),
+                    "/subjects/users%2Fv1-value/versions/latest" => {
+                        ("200 OK", json!({ "id": 1, "schema": USER_SCHEMA }))
+                    }
+                    "/schemas/ids/2" => (
+                        "200 OK",
+                        json!({ "schema": "syntax = \"proto3\";", "schemaType": "PROTOBUF" }),
+                    ),
+                    _ => (
+                        "404 Not Found",
+                        json!({ "error_code": 40403, "message": "Schema not found" }),
+                    ),
+                };
+                let body = body.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn create_client(url: String, username: Option<&str>) -> SchemaRegistryClient {
+        SchemaRegistryClient::new(SchemaRegistryConfig {
+            url,
+            username: username.map(|username| username.to_owned()),
+            password: username.map(|_| "secret".to_owned()),
+        })
+        .unwrap()
+    }
+
+    fn count_requests(requests: &Requests, path: &str) -> usize {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(request_line, _)| request_line.contains(&format!("{} ", path)))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn decodes_framed_payload_and_caches_the_schema() {
+        let (url, requests) = start_stub_registry();
+        let client = create_client(url, None);
+
+        // Confluent frame of schema id 1 followed by the avro string "Ada"
+        let payload = [0, 0, 0, 0, 1, 6, b'A', b'd', b'a'];
+        let json = decode_avro(&client, &payload).await.unwrap();
+        assert_eq!(json, Some(json!({ "name": "Ada" })));
+
+        let json = decode_avro(&client, &payload).await.unwrap();
+        assert_eq!(json, Some(json!({ "name": "Ada" })));
+        assert_eq!(count_requests(&requests, "/schemas/ids/1"), 1);
+    }
+
+    #[tokio::test]
+    async fn encodes_the_subject_and_caches_the_latest_version() {
+        let (url, requests) = start_stub_registry();
+        let client = create_client(url, None);
+
+        let payload = encode_avro(&client, "users/v1-value", None, json!({ "name": "Ada" }))
+            .await
+            .unwrap();
+        assert_eq!(payload, vec![0, 0, 0, 0, 1, 6, b'A', b'd', b'a']);
+
+        encode_avro(&client, "users/v1-value", None, json!({ "name": "Ada" }))
+            .await
+            .unwrap();
+        assert_eq!(
+            count_requests(&requests, "/subjects/users%2Fv1-value/versions/latest"),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn caches_unknown_ids_and_unsupported_types() {
+        let (url, requests) = start_stub_registry();
+        let client = create_client(url, None);
+
+        let error = client.get_schema(404).await.err().unwrap();
+        assert!(error.contains("404"), "{}", error);
+        assert!(client.get_schema(404).await.is_err());
+        assert_eq!(count_requests(&requests, "/schemas/ids/404"), 1);
+
+        let error = client.get_schema(2).await.err().unwrap();
+        assert_eq!(error, "Unsupported schema type PROTOBUF");
+        assert!(client.get_schema(2).await.is_err());
+        assert_eq!(count_requests(&requests, "/schemas/ids/2"), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_basic_auth_credentials() {
+        let (url, requests) = start_stub_registry();
+        let client = create_client(url, Some("registry"));
+
+        client.get_schema(1).await.unwrap();
+
+        // base64 of registry:secret
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].1.as_deref(), Some("Basic cmVnaXN0cnk6c2VjcmV0"));
+    }
+
+    #[test]
+    fn splits_confluent_frames_only() {
+        assert_eq!(split_confluent_frame(&[1, 0, 0, 0, 1, 2]), None);
+        assert_eq!(split_confluent_frame(&[0, 0, 0, 1]), None);
+        assert_eq!(
+            split_confluent_frame(&[0, 0, 0, 1, 2, 42]),
+            Some((258, &[42u8][..]))
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::BorrowedMessage;
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use serde::Serialize;
+use tauri::Window;
+use tokio::time::{Duration, Instant};
+
+use crate::decoders::TopicDecoders;
+use crate::filters::MessageMatcher;
+use crate::messages::{get_position, process_message, KafkaMessageResponse, PartitionRange};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchProgress {
+    pub scanned: i64,
+    pub total: i64,
+    pub matched: u64,
+    pub percentage: f64,
+    /// Only for the scans producing what they read, like copies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed: Option<u64>,
+    /// Stopped before reading every partition to its end
+    pub cancelled: bool,
+}
+
+/// Receives the messages of a scan matching its filter
+#[async_trait]
+pub trait ScanHandler: Sync {
+    async fn on_message(
+        &self,
+        message: &BorrowedMessage<'_>,
+        response: KafkaMessageResponse,
+    ) -> Result<(), String>;
+
+    /// Delivered and failed records so far, for the scans producing what they read
+    fn deliveries(&self) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+struct CallbackHandler<F>(F);
+
+#[async_trait]
+impl<F> ScanHandler for CallbackHandler<F>
+where
+    F: Fn(&BorrowedMessage<'_>, KafkaMessageResponse) -> Result<(), String> + Sync,
+{
+    async fn on_message(
+        &self,
+        message: &BorrowedMessage<'_>,
+        response: KafkaMessageResponse,
+    ) -> Result<(), String> {
+        (self.0)(message, response)
+    }
+}
+
+/// Reads a single partition from a start offset up to the high watermark, with its own consumer
+/// so that the partitions of a topic can be read in parallel
+pub struct PartitionReader {
+    consumer: StreamConsumer,
+    topic: String,
+    partition: i32,
+    start: i64,
+    end: i64,
+    position: AtomicI64,
+}
+
+impl PartitionReader {
+    /// End is excluded, usually the high watermark when starting to read
+    pub fn new(
+        common_config: &ClientConfig,
+        topic: &str,
+        partition: i32,
+        start: i64,
+        end: i64,
+    ) -> Result<PartitionReader, String> {
+        let consumer = create_reader_consumer(common_config)?;
+
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition_offset(topic, partition, Offset::Offset(start))
+            .unwrap();
+        consumer.assign(&tpl).map_err(|err| {
+            format!(
+                "Could not assign topic: {}, partition: {}\n\nError: {}",
+                topic,
+                partition,
+                err.to_string()
+            )
+        })?;
+
+        Ok(PartitionReader {
+            consumer,
+            topic: topic.to_owned(),
+            partition,
+            start,
+            end,
+            position: AtomicI64::new(start),
+        })
+    }
+
+    pub fn partition(&self) -> i32 {
+        self.partition
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position.load(Ordering::Relaxed) >= self.end
+    }
+
+    /// Offsets read so far, including the ones without a message like transaction markers
+    pub fn scanned(&self) -> i64 {
+        self.position.load(Ordering::Relaxed).min(self.end) - self.start
+    }
+
+    pub fn total(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// Next message of the range, none when the range is finished or nothing came in time
+    pub async fn next(&self) -> Result<Option<BorrowedMessage<'_>>, String> {
+        if self.is_finished() {
+            return Ok(None);
+        }
+
+        let timeout = Duration::from_secs(3);
+        match tokio::time::timeout(timeout, self.consumer.recv()).await {
+            Ok(Ok(message)) => {
+                self.position.store(message.offset() + 1, Ordering::Relaxed);
+                if message.offset() >= self.end {
+                    return Ok(None);
+                }
+                Ok(Some(message))
+            }
+            Ok(Err(err)) => Err(format!(
+                "Could not read topic: {}, partition: {}\n\nError: {}",
+                self.topic,
+                self.partition,
+                err.to_string()
+            )),
+            Err(_) => {
+                // The last offsets might never be delivered, e.g. transaction markers
+                let position = get_position(&self.consumer, &self.topic, self.partition)?;
+                if position > self.position.load(Ordering::Relaxed) {
+                    self.position.store(position, Ordering::Relaxed);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+pub(crate) fn create_reader_consumer(
+    common_config: &ClientConfig,
+) -> Result<StreamConsumer, String> {
+    common_config
+        .clone()
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .map_err(|err| format!("Could not create consumer: {}", err.to_string()))
+}
+
+/// One reader for every non empty partition of the topic, from the low to the high watermark
+/// unless narrowed down by the ranges
+pub fn create_topic_readers(
+    common_config: &ClientConfig,
+    topic: &str,
+    ranges: Option<&[PartitionRange]>,
+) -> Result<Vec<PartitionReader>, String> {
+    let consumer = create_reader_consumer(common_config)?;
+    let metadata = consumer
+        .fetch_metadata(Some(topic), Duration::from_secs(30))
+        .map_err(|err| {
+            format!(
+                "Could not fetch topic metadada for topic: {}\n\nError: {}",
+                topic,
+                err.to_string()
+            )
+        })?;
+
+    let topic_partitions: Vec<i32> = metadata
+        .topics()
+        .first()
+        .unwrap()
+        .partitions()
+        .iter()
+        .map(|partition| partition.id())
+        .collect();
+
+    let ranges = match ranges {
+        Some(ranges) => ranges.to_vec(),
+        None => topic_partitions
+            .iter()
+            .map(|partition| PartitionRange {
+                partition: *partition,
+                start_offset: None,
+                end_offset: None,
+            })
+            .collect(),
+    };
+
+    let mut readers = vec![];
+    for range in ranges {
+        if !topic_partitions.contains(&range.partition) {
+            return Err(format!(
+                "Partition {} not found in topic: {}",
+                range.partition, topic
+            ));
+        }
+
+        let (low, high) = consumer
+            .fetch_watermarks(topic, range.partition, Duration::from_secs(30))
+            .map_err(|err| {
+                format!(
+                    "Could not fetch watermarks for topic: {}, partition: {}\n\nError: {}",
+                    topic,
+                    range.partition,
+                    err.to_string()
+                )
+            })?;
+
+        let start = range.start_offset.map_or(low, |offset| offset.max(low));
+        let end = range
+            .end_offset
+            .map_or(high, |offset| (offset + 1).min(high));
+        if end > start {
+            readers.push(PartitionReader::new(
+                common_config,
+                topic,
+                range.partition,
+                start,
+                end,
+            )?);
+        }
+    }
+
+    Ok(readers)
+}
+
+/// Scan the whole topic looking for the messages matching the filter
+pub async fn search_messages(
+    window: Window,
+    common_config: ClientConfig,
+    topic: String,
+    decoders: &TopicDecoders<'_>,
+    matcher: &MessageMatcher,
+    id: String,
+) -> Result<(), String> {
+    let readers = create_topic_readers(&common_config, &topic, None)?;
+
+    scan_topic(
+        &window,
+        &readers,
+        decoders,
+        Some(matcher),
+        "Search",
+        &id,
+        |_, message| {
+            window
+                .emit(&format!("onSearchMessage-{}", id), message)
+                .unwrap();
+            Ok(())
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Read the partitions in parallel handing the matching messages to the callback,
+/// progress is emitted as `on{job}Progress-{id}` and the scan is stopped by `off{job}-{id}`
+pub async fn scan_topic(
+    window: &Window,
+    readers: &[PartitionReader],
+    decoders: &TopicDecoders<'_>,
+    matcher: Option<&MessageMatcher>,
+    job: &str,
+    id: &str,
+    on_message: impl Fn(&BorrowedMessage<'_>, KafkaMessageResponse) -> Result<(), String> + Sync,
+) -> Result<SearchProgress, String> {
+    let handler = CallbackHandler(on_message);
+    scan_topic_with(window, readers, decoders, matcher, job, id, &handler).await
+}
+
+/// Same as `scan_topic` for handlers that have to wait, e.g. for room in the producer queue
+pub async fn scan_topic_with(
+    window: &Window,
+    readers: &[PartitionReader],
+    decoders: &TopicDecoders<'_>,
+    matcher: Option<&MessageMatcher>,
+    job: &str,
+    id: &str,
+    handler: &dyn ScanHandler,
+) -> Result<SearchProgress, String> {
+    let keep_scanning = Arc::new(RwLock::new(true));
+    let keep_scanning_clone = keep_scanning.clone();
+    window.once(format!("off{}-{}", job, id), move |_| {
+        *keep_scanning_clone.write().unwrap() = false;
+    });
+
+    let matched = AtomicU64::new(0);
+    let last_progress = Mutex::new(Instant::now());
+
+    let get_progress = || {
+        let scanned: i64 = readers.iter().map(|reader| reader.scanned()).sum();
+        let total: i64 = readers.iter().map(|reader| reader.total()).sum();
+        let percentage = if total > 0 {
+            scanned as f64 * 100.0 / total as f64
+        } else {
+            100.0
+        };
+        let (delivered, failed) = match handler.deliveries() {
+            Some((delivered, failed)) => (Some(delivered), Some(failed)),
+            None => (None, None),
+        };
+
+        SearchProgress {
+            scanned,
+            total,
+            matched: matched.load(Ordering::Relaxed),
+            percentage,
+            delivered,
+            failed,
+            cancelled: !*keep_scanning.read().unwrap(),
+        }
+    };
+    let emit_progress = || {
+        window
+            .emit(&format!("on{}Progress-{}", job, id), get_progress())
+            .unwrap();
+    };
+
+    let scan = ScanContext {
+        decoders,
+        matcher,
+        keep_scanning: &keep_scanning,
+        matched: &matched,
+        last_progress: &last_progress,
+        emit_progress: &emit_progress,
+        handler,
+    };
+    try_join_all(readers.iter().map(|reader| scan_partition(reader, &scan))).await?;
+
+    let progress = get_progress();
+    emit_progress();
+
+    Ok(progress)
+}
+
+struct ScanContext<'a> {
+    decoders: &'a TopicDecoders<'a>,
+    matcher: Option<&'a MessageMatcher>,
+    keep_scanning: &'a RwLock<bool>,
+    matched: &'a AtomicU64,
+    last_progress: &'a Mutex<Instant>,
+    emit_progress: &'a (dyn Fn() + Sync),
+    handler: &'a dyn ScanHandler,
+}
+
+async fn scan_partition(reader: &PartitionReader, scan: &ScanContext<'_>) -> Result<(), String> {
+    while *scan.keep_scanning.read().unwrap() && !reader.is_finished() {
+        if let Some(message) = reader.next().await? {
+            let message_result = process_message(&message, scan.decoders)
+                .await
+                .map_err(|err| {
+                    format!(
+                        "Could not process message for topic: {}, partition: {}, offset: {}\n\nError: {}",
+                        message.topic(),
+                        message.partition(),
+                        message.offset(),
+                        err
+                    )
+                })?;
+
+            let is_match = scan
+                .matcher
+                .map_or(true, |matcher| message_result.matches(matcher));
+            if is_match {
+                scan.matched.fetch_add(1, Ordering::Relaxed);
+                scan.handler.on_message(&message, message_result).await?;
+            }
+        }
+
+        let should_emit = {
+            let mut last_progress = scan.last_progress.lock().unwrap();
+            let should_emit = last_progress.elapsed() >= Duration::from_secs(1);
+            if should_emit {
+                *last_progress = Instant::now();
+            }
+            should_emit
+        };
+        if should_emit {
+            (scan.emit_progress)();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock as StdRwLock};
+
+use tokio::sync::RwLock;
+
+/// Listen running with its own consumer, stopped by `offMessage-{id}` or when its window closes
+pub struct ListenSession {
+    window: String,
+    keep_listening: Arc<StdRwLock<bool>>,
+}
+
+impl ListenSession {
+    pub fn new(window: String) -> ListenSession {
+        ListenSession {
+            window,
+            keep_listening: Arc::new(StdRwLock::new(true)),
+        }
+    }
+
+    pub fn keep_listening(&self) -> Arc<StdRwLock<bool>> {
+        self.keep_listening.clone()
+    }
+
+    pub fn stop(&self) {
+        *self.keep_listening.write().unwrap() = false;
+    }
+}
+
+pub type ListenSessions = Arc<RwLock<HashMap<String, ListenSession>>>;
+
+/// The sessions remove themselves from the registry once their listen returns
+pub async fn stop_window_sessions(sessions: &ListenSessions, window: &str) {
+    for session in sessions.read().await.values() {
+        if session.window == window {
+            session.stop();
+        }
+    }
+}
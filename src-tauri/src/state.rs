@@ -1,4 +1,4 @@
-use std::{fs::create_dir_all, io::ErrorKind, path::Path};
+use std::{collections::HashMap, fs::create_dir_all, io::ErrorKind, path::Path, sync::Arc};
 
 use jfs::{Config, Store};
 use rdkafka::{
@@ -7,8 +7,14 @@ use rdkafka::{
 };
 use serde_json::{json, Value};
 use tauri::api::path::home_dir;
-use tokio::sync::RwLock;
-
+use tokio::sync::{Mutex, RwLock};
+
+use crate::autosend::AutosendJobs;
+use crate::producers::ReportingProducers;
+use crate::protobuf::ProtobufRegistry;
+use crate::schema_registry::SchemaRegistryClient;
+use crate::sessions::ListenSessions;
+use crate::transactions::TransactionalProducer;
 use crate::utils::{get_env, Environment};
 
 pub struct KafkaState {
@@ -16,25 +22,52 @@ pub struct KafkaState {
     pub admin: RwLock<Option<AdminClient<DefaultClientContext>>>,
     pub consumer: RwLock<Option<StreamConsumer>>,
     pub producer: RwLock<Option<FutureProducer>>,
+    pub producer_config: RwLock<Option<ClientConfig>>,
+    /// Producers of single messages, whose deliveries report their timestamp
+    pub reporting_producers: RwLock<Option<Arc<ReportingProducers>>>,
+    /// Created by the first transaction of the connection, transactions wait for each other
+    pub transactional_producer: Mutex<Option<TransactionalProducer>>,
+    /// Shared so that commands can keep using them without holding the lock
+    pub schema_registry: RwLock<Option<Arc<SchemaRegistryClient>>>,
+    pub protobuf: RwLock<Option<Arc<ProtobufRegistry>>>,
+    /// Autosends keep the producer they were started with, even when the connection changes
+    pub autosends: AutosendJobs,
+    pub listen_sessions: ListenSessions,
 }
 
 pub fn init_kafka() -> KafkaState {
     let admin = RwLock::new(None);
     let consumer = RwLock::new(None);
     let producer = RwLock::new(None);
+    let producer_config = RwLock::new(None);
+    let reporting_producers = RwLock::new(None);
+    let transactional_producer = Mutex::new(None);
     let common_config = RwLock::new(None);
+    let schema_registry = RwLock::new(None);
+    let protobuf = RwLock::new(None);
+    let autosends = Arc::new(RwLock::new(HashMap::new()));
+    let listen_sessions = Arc::new(RwLock::new(HashMap::new()));
 
     KafkaState {
         admin,
         consumer,
         producer,
+        producer_config,
+        reporting_producers,
+        transactional_producer,
         common_config,
+        schema_registry,
+        protobuf,
+        autosends,
+        listen_sessions,
     }
 }
 
 pub struct StorageState {
     pub settings: Store,
     pub messages: Store,
+    /// Destructive actions like tombstoning keys
+    pub audit: Store,
 }
 
 pub fn get_app_dir() -> Result<String, String> {
@@ -52,7 +85,7 @@ pub fn get_app_dir_with_env() -> Result<String, String> {
     match get_env() {
         Environment::Release => Ok(format!("{}/release", app_dir)),
         Environment::Dev => Ok(format!("{}/dev", app_dir)),
-        Environment::E2E => Ok(format!("{}/e2e", app_dir))
+        Environment::E2E => Ok(format!("{}/e2e", app_dir)),
     }
 }
 
@@ -94,6 +127,7 @@ pub fn init_storage() -> Result<StorageState, String> {
 
     set_storage_default(&settings, "CONNECTIONS", &json!([]))?;
     set_storage_default(&settings, "MESSAGES", &json!(20))?;
+    set_storage_default(&settings, "DECODERS", &json!([]))?;
 
     let messages = Store::new_with_cfg(
         format!("{}/messages.json", config_dir_with_env),
@@ -106,7 +140,19 @@ pub fn init_storage() -> Result<StorageState, String> {
         )
     })?;
 
-    Ok(StorageState { settings, messages })
+    let audit = Store::new_with_cfg(format!("{}/audit.json", config_dir_with_env), store_config)
+        .map_err(|err| {
+            format!(
+                "Unexpected error, could create storage file; err: {}",
+                err.to_string()
+            )
+        })?;
+
+    Ok(StorageState {
+        settings,
+        messages,
+        audit,
+    })
 }
 
 fn set_storage_default(store: &Store, key: &str, value: &Value) -> Result<(), String> {
@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+const FIRST_NAMES_FEMALE: &[&str] = &[
+    "Alice", "Chiara", "Emma", "Giulia", "Hannah", "Isabella", "Julia", "Laura", "Maria", "Olivia",
+    "Sara", "Sofia",
+];
+const FIRST_NAMES_MALE: &[&str] = &[
+    "Alessandro",
+    "Daniel",
+    "David",
+    "Francesco",
+    "James",
+    "John",
+    "Leonardo",
+    "Lorenzo",
+    "Lucas",
+    "Marco",
+    "Michael",
+    "Noah",
+];
+const LAST_NAMES: &[&str] = &[
+    "Bianchi", "Brown", "Colombo", "Esposito", "Garcia", "Johnson", "Jones", "Miller", "Ricci",
+    "Romano", "Rossi", "Smith", "Williams",
+];
+const COUNTRIES: &[&str] = &[
+    "Australia",
+    "Brazil",
+    "Canada",
+    "France",
+    "Germany",
+    "India",
+    "Italy",
+    "Japan",
+    "Mexico",
+    "Spain",
+    "United Kingdom",
+    "United States",
+];
+const CITIES: &[&str] = &[
+    "Amsterdam",
+    "Berlin",
+    "Chicago",
+    "London",
+    "Madrid",
+    "Milan",
+    "New York",
+    "Paris",
+    "Rome",
+    "Sydney",
+    "Tokyo",
+    "Toronto",
+];
+const EMAIL_DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet",
+    "kilo", "lima",
+];
+
+const ALPHA: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMERIC: &[u8] = b"0123456789";
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+// Same as Number.MAX_SAFE_INTEGER, the default maximum of faker.number.int()
+const MAX_SAFE_INTEGER: i64 = 9007199254740991;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RenderedMessage {
+    pub headers: Option<HashMap<String, Option<String>>>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+}
+
+/// Renders the `{{...}}` placeholders of a message with the same syntax as the frontend,
+/// e.g. `{{faker.person.firstName('female')}}` or `{{key.id}}`.
+/// The key is rendered first, the value can reference the key and the headers both of them.
+/// `sequence` is the position of the message in the job, available as `{{sequence}}`
+pub fn render_message(
+    headers: Option<&HashMap<String, Option<String>>>,
+    key: Option<&str>,
+    value: Option<&str>,
+    sequence: u64,
+) -> Result<RenderedMessage, String> {
+    let mut context = Map::new();
+
+    let key = match key {
+        Some(key) => {
+            let rendered = render_template(key, &context, sequence)
+                .map_err(|err| format!("Template error in key: {}", err))?;
+            context.insert("key".to_owned(), parse_rendered(&rendered));
+            Some(rendered)
+        }
+        None => None,
+    };
+
+    let value = match value {
+        Some(value) => {
+            let rendered = render_template(value, &context, sequence)
+                .map_err(|err| format!("Template error in value: {}", err))?;
+            context.insert("value".to_owned(), parse_rendered(&rendered));
+            Some(rendered)
+        }
+        None => None,
+    };
+
+    let headers = match headers {
+        Some(headers) => {
+            let mut rendered_headers = HashMap::new();
+            for (header_key, header_value) in headers {
+                let rendered = match header_value {
+                    Some(header_value) => Some(
+                        render_template(header_value, &context, sequence).map_err(|err| {
+                            format!("Template error in header {}: {}", header_key, err)
+                        })?,
+                    ),
+                    None => None,
+                };
+                rendered_headers.insert(header_key.clone(), rendered);
+            }
+            Some(rendered_headers)
+        }
+        None => None,
+    };
+
+    Ok(RenderedMessage {
+        headers,
+        key,
+        value,
+    })
+}
+
+/// Rendered key and value are referenced as JSON when possible, like the frontend does
+fn parse_rendered(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_owned()))
+}
+
+fn render_template(
+    template: &str,
+    context: &Map<String, Value>,
+    sequence: u64,
+) -> Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after_start = &rest[start + 2..];
+        let end = after_start
+            .find("}}")
+            .ok_or_else(|| format!("unclosed placeholder {}", &rest[start..]))?;
+        let expression = &after_start[..end];
+
+        let value = evaluate(expression, context, sequence)
+            .map_err(|err| format!("{{{{{}}}}}: {}", expression, err))?;
+        match value {
+            Value::String(text) => rendered.push_str(&text),
+            value => rendered.push_str(&value.to_string()),
+        }
+
+        rest = &after_start[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+fn evaluate(
+    expression: &str,
+    context: &Map<String, Value>,
+    sequence: u64,
+) -> Result<Value, String> {
+    let mut parser = Parser::new(expression);
+    let (path, args) = parser.parse_expression()?;
+
+    match path.first().map(|segment| segment.as_str()) {
+        Some("key") | Some("value") => {
+            let mut current = context
+                .get(&path[0])
+                .ok_or_else(|| format!("{} is not available here", path[0]))?;
+            for segment in &path[1..] {
+                current = match current {
+                    Value::Object(object) => object.get(segment),
+                    Value::Array(array) => segment
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| array.get(index)),
+                    _ => None,
+                }
+                .ok_or_else(|| format!("{} not found", path.join(".")))?;
+            }
+            Ok(current.clone())
+        }
+        _ => generate(&path.join("."), &args, sequence),
+    }
+}
+
+fn generate(name: &str, args: &[Value], sequence: u64) -> Result<Value, String> {
+    let mut rng = rand::thread_rng();
+
+    let value = match name {
+        "sequence" => Value::from(sequence),
+        "now" => Value::String(format_iso8601(now_millis())),
+        "timestamp" => Value::from(now_millis()),
+
+        "faker.string.uuid" | "faker.datatype.uuid" => Value::String(Uuid::new_v4().to_string()),
+        "faker.string.alpha" => Value::String(random_string(ALPHA, get_length(args)?)),
+        "faker.string.numeric" => Value::String(random_string(NUMERIC, get_length(args)?)),
+        "faker.string.alphanumeric" => {
+            Value::String(random_string(ALPHANUMERIC, get_length(args)?))
+        }
+
+        "faker.number.int" => {
+            let (min, max) = get_int_bounds(args)?;
+            Value::from(rng.gen_range(min..=max))
+        }
+        "faker.number.float" => {
+            let options = args.first().and_then(|arg| arg.as_object());
+            let get = |name: &str| options.and_then(|options| options.get(name)?.as_f64());
+            let min = get("min").unwrap_or(0.0);
+            let max = get("max").unwrap_or(1.0);
+            if min > max {
+                return Err(format!("min {} is greater than max {}", min, max));
+            }
+            let mut float = if min == max {
+                min
+            } else {
+                rng.gen_range(min..max)
+            };
+            if let Some(digits) = get("fractionDigits")
+                .or_else(|| get("precision").map(|precision| -precision.log10()))
+            {
+                let factor = 10f64.powi(digits.round() as i32);
+                float = (float * factor).round() / factor;
+            }
+            Value::from(float)
+        }
+        "faker.datatype.boolean" => Value::Bool(rng.gen()),
+
+        "faker.helpers.arrayElement" => match args.first() {
+            Some(Value::Array(elements)) if !elements.is_empty() => {
+                elements.choose(&mut rng).unwrap().clone()
+            }
+            _ => return Err("expected a non empty array".into()),
+        },
+
+        "faker.person.firstName" => {
+            let names = match args.first().and_then(|arg| arg.as_str()) {
+                Some("female") => FIRST_NAMES_FEMALE,
+                Some("male") => FIRST_NAMES_MALE,
+                Some(sex) => return Err(format!("unknown sex {}", sex)),
+                None if rng.gen() => FIRST_NAMES_FEMALE,
+                None => FIRST_NAMES_MALE,
+            };
+            Value::String(pick(names))
+        }
+        "faker.person.lastName" => Value::String(pick(LAST_NAMES)),
+        "faker.person.fullName" => {
+            let names = if rng.gen() {
+                FIRST_NAMES_FEMALE
+            } else {
+                FIRST_NAMES_MALE
+            };
+            Value::String(format!("{} {}", pick(names), pick(LAST_NAMES)))
+        }
+        "faker.internet.email" => Value::String(format!(
+            "{}.{}{}@{}",
+            pick(FIRST_NAMES_FEMALE).to_lowercase(),
+            pick(LAST_NAMES).to_lowercase(),
+            rng.gen_range(1..100),
+            pick(EMAIL_DOMAINS)
+        )),
+        "faker.location.country" => Value::String(pick(COUNTRIES)),
+        "faker.location.city" => Value::String(pick(CITIES)),
+        "faker.lorem.word" => Value::String(pick(WORDS)),
+
+        "faker.date.recent" => random_date(-DAY_MILLIS, 0),
+        "faker.date.soon" => random_date(0, DAY_MILLIS),
+        "faker.date.past" => random_date(-365 * DAY_MILLIS, 0),
+        "faker.date.future" => random_date(0, 365 * DAY_MILLIS),
+
+        name => return Err(format!("unknown generator {}", name)),
+    };
+
+    Ok(value)
+}
+
+fn pick(values: &[&str]) -> String {
+    values.choose(&mut rand::thread_rng()).unwrap().to_string()
+}
+
+fn random_string(charset: &[u8], length: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..length)
+        .map(|_| *charset.choose(&mut rng).unwrap() as char)
+        .collect()
+}
+
+/// Length given directly or as `{length: n}`, 1 when not given
+fn get_length(args: &[Value]) -> Result<usize, String> {
+    let length = match args.first() {
+        None => return Ok(1),
+        Some(Value::Object(options)) => options.get("length").and_then(|length| length.as_u64()),
+        Some(length) => length.as_u64(),
+    };
+
+    length
+        .map(|length| length as usize)
+        .ok_or_else(|| "expected a length".to_owned())
+}
+
+/// Bounds given as a max or as `{min: a, max: b}`, like faker.number.int
+fn get_int_bounds(args: &[Value]) -> Result<(i64, i64), String> {
+    let (min, max) = match args.first() {
+        None => (0, MAX_SAFE_INTEGER),
+        Some(Value::Object(options)) => (
+            options.get("min").and_then(|min| min.as_i64()).unwrap_or(0),
+            options
+                .get("max")
+                .and_then(|max| max.as_i64())
+                .unwrap_or(MAX_SAFE_INTEGER),
+        ),
+        Some(max) => (0, max.as_i64().ok_or("expected an integer")?),
+    };
+
+    if min > max {
+        return Err(format!("min {} is greater than max {}", min, max));
+    }
+    Ok((min, max))
+}
+
+/// Random date between now plus the given offsets, as an ISO 8601 string
+fn random_date(from_offset: i64, to_offset: i64) -> Value {
+    let now = now_millis();
+    let millis = rand::thread_rng().gen_range(now + from_offset..=now + to_offset);
+    Value::String(format_iso8601(millis))
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn format_iso8601(millis: i64) -> String {
+    let days = millis.div_euclid(DAY_MILLIS);
+    let day_millis = millis.rem_euclid(DAY_MILLIS);
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        day_millis / 3_600_000,
+        day_millis / 60_000 % 60,
+        day_millis / 1000 % 60,
+        day_millis % 1000
+    )
+}
+
+/// Parser for the placeholder expressions: a dotted path, optionally called with literal
+/// arguments, e.g. `faker.number.int({ min: 1, max: 10 })` or `key.items[0].id`
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser { input, position: 0 }
+    }
+
+    fn parse_expression(&mut self) -> Result<(Vec<String>, Vec<Value>), String> {
+        // The frontend evaluates the placeholders as `this.<expression>`
+        self.skip_whitespace();
+        if self.rest().starts_with("this.") {
+            self.position += 5;
+        }
+
+        let mut path = vec![self.parse_identifier()?];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('.') => {
+                    self.position += 1;
+                    path.push(self.parse_identifier()?);
+                }
+                Some('[') => {
+                    self.position += 1;
+                    let index = self.parse_literal()?;
+                    self.expect(']')?;
+                    path.push(match index {
+                        Value::String(index) => index,
+                        index => index.to_string(),
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        let mut args = vec![];
+        if self.peek() == Some('(') {
+            self.position += 1;
+            args = self.parse_list(')')?;
+        }
+
+        self.skip_whitespace();
+        if self.position < self.input.len() {
+            return Err(format!("unexpected {}", self.location()));
+        }
+
+        Ok((path, args))
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let length = self
+            .rest()
+            .find(|char: char| !(char.is_alphanumeric() || char == '_' || char == '$'))
+            .unwrap_or_else(|| self.rest().len());
+        if length == 0 {
+            return Err(format!("expected a name at {}", self.location()));
+        }
+
+        let identifier = self.rest()[..length].to_owned();
+        self.position += length;
+        Ok(identifier)
+    }
+
+    fn parse_list(&mut self, close: char) -> Result<Vec<Value>, String> {
+        let mut values = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                self.position += 1;
+                return Ok(values);
+            }
+
+            values.push(self.parse_literal()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(char) if char == close => {}
+                _ => return Err(format!("expected , or {} at {}", close, self.location())),
+            }
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(quote @ '\'') | Some(quote @ '"') | Some(quote @ '`') => {
+                self.position += 1;
+                let length = self
+                    .rest()
+                    .find(quote)
+                    .ok_or("unclosed string".to_owned())?;
+                let text = self.rest()[..length].to_owned();
+                self.position += length + 1;
+                Ok(Value::String(text))
+            }
+            Some('[') => {
+                self.position += 1;
+                Ok(Value::Array(self.parse_list(']')?))
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut object = Map::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some('}') {
+                        self.position += 1;
+                        return Ok(Value::Object(object));
+                    }
+
+                    let name = match self.peek() {
+                        Some('\'') | Some('"') => match self.parse_literal()? {
+                            Value::String(name) => name,
+                            _ => unreachable!(),
+                        },
+                        _ => self.parse_identifier()?,
+                    };
+                    self.expect(':')?;
+                    object.insert(name, self.parse_literal()?);
+
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some('}') => {}
+                        _ => return Err(format!("expected , or }} at {}", self.location())),
+                    }
+                }
+            }
+            _ => {
+                let length = self
+                    .rest()
+                    .find(|char: char| {
+                        matches!(char, ',' | ')' | ']' | '}') || char.is_whitespace()
+                    })
+                    .unwrap_or_else(|| self.rest().len());
+                let token = &self.rest()[..length];
+                let value = match token {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "null" | "undefined" => Value::Null,
+                    number => serde_json::from_str::<Value>(number)
+                        .ok()
+                        .filter(|value| value.is_number())
+                        .ok_or_else(|| format!("unexpected {}", self.location()))?,
+                };
+                self.position += length;
+                Ok(value)
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(format!("expected {} at {}", expected, self.location()));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.input.len() - trimmed.len();
+    }
+
+    fn location(&self) -> String {
+        match self.rest() {
+            "" => "end of expression".to_owned(),
+            rest => format!("'{}'", rest),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_value(value: &str) -> Result<String, String> {
+        render_message(None, None, Some(value), 0).map(|rendered| rendered.value.unwrap())
+    }
+
+    #[test]
+    fn leaves_plain_text_untouched() {
+        assert_eq!(render_value(r#"{"id": 1}"#).unwrap(), r#"{"id": 1}"#);
+    }
+
+    #[test]
+    fn references_the_rendered_key_and_value() {
+        let headers = HashMap::from([(
+            "trace".to_owned(),
+            Some("{{key.id}}/{{value.items[1]}}".to_owned()),
+        )]);
+        let rendered = render_message(
+            Some(&headers),
+            Some(r#"{"id": "order-{{sequence}}"}"#),
+            Some(r#"{"key": "{{this.key.id}}", "items": ["a", "b"]}"#),
+            7,
+        )
+        .unwrap();
+
+        assert_eq!(rendered.key.unwrap(), r#"{"id": "order-7"}"#);
+        assert_eq!(
+            rendered.value.unwrap(),
+            r#"{"key": "order-7", "items": ["a", "b"]}"#
+        );
+        assert_eq!(
+            rendered.headers.unwrap()["trace"].as_deref(),
+            Some("order-7/b")
+        );
+    }
+
+    #[test]
+    fn calls_generators_with_literal_arguments() {
+        assert_eq!(
+            render_value("{{faker.number.int({ min: 5, max: 5 })}}").unwrap(),
+            "5"
+        );
+        assert_eq!(
+            render_value("{{ faker.helpers.arrayElement(['only']) }}").unwrap(),
+            "only"
+        );
+
+        let numeric = render_value("{{faker.string.numeric({ length: 6 })}}").unwrap();
+        assert_eq!(numeric.len(), 6);
+        assert!(numeric.chars().all(|char| char.is_ascii_digit()));
+
+        let name = render_value(r#"{{faker.person.firstName("female")}}"#).unwrap();
+        assert!(FIRST_NAMES_FEMALE.contains(&name.as_str()));
+
+        let uuid = render_value("{{faker.string.uuid()}}").unwrap();
+        assert!(Uuid::parse_str(&uuid).is_ok());
+    }
+
+    #[test]
+    fn formats_dates_as_iso8601() {
+        assert_eq!(format_iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+        assert_eq!(format_iso8601(-1), "1969-12-31T23:59:59.999Z");
+    }
+
+    #[test]
+    fn reports_where_the_template_is_invalid() {
+        assert_eq!(
+            render_message(None, Some("id-{{sequence"), None, 0).unwrap_err(),
+            "Template error in key: unclosed placeholder {{sequence"
+        );
+        assert_eq!(
+            render_message(None, Some("{{value.id}}"), Some("{}"), 0).unwrap_err(),
+            "Template error in key: {{value.id}}: value is not available here"
+        );
+        assert_eq!(
+            render_value("{{faker.unknown.thing}}").unwrap_err(),
+            "Template error in value: {{faker.unknown.thing}}: unknown generator faker.unknown.thing"
+        );
+        assert_eq!(
+            render_value("{{faker.number.int({ min: 2, max: 1 })}}").unwrap_err(),
+            "Template error in value: {{faker.number.int({ min: 2, max: 1 })}}: min 2 is greater than max 1"
+        );
+        assert_eq!(
+            render_value("{{faker.number.int(1}}").unwrap_err(),
+            "Template error in value: {{faker.number.int(1}}: expected , or ) at end of expression"
+        );
+
+        let headers = HashMap::from([("trace".to_owned(), Some("{{key.missing}}".to_owned()))]);
+        assert_eq!(
+            render_message(Some(&headers), Some("{}"), None, 0).unwrap_err(),
+            "Template error in header trace: {{key.missing}}: key.missing not found"
+        );
+    }
+}
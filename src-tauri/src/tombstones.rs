@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::join_all;
+use jfs::Store;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use rdkafka::{ClientConfig, Message};
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+
+use crate::compacted::LatestRecords;
+use crate::decoders::TopicDecoders;
+use crate::encoding::PayloadEncoding;
+use crate::filters::{MessageFilter, MessageMatcher};
+use crate::messages::KafkaMessageResponse;
+use crate::search::{create_topic_readers, scan_topic};
+use crate::storage::save_in_store;
+
+/// Keys to erase, exact keys and filter both have to match when given together.
+/// The filter applies to the latest record of each key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TombstoneSelector {
+    pub keys: Option<Vec<String>>,
+    pub filter: Option<MessageFilter>,
+}
+
+/// What to erase and whether to only list it
+#[derive(Deserialize, Debug, Clone)]
+pub struct TombstoneOptions {
+    pub selector: TombstoneSelector,
+    pub dry_run: bool,
+    /// Used for the fields of the records no decoder applies to
+    pub encoding: Option<PayloadEncoding>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TombstoneCandidate {
+    pub topic: String,
+    /// Tombstones go to the partition of the latest record, whatever the partitioner
+    pub partition: i32,
+    /// Offset of the latest record of the key
+    pub offset: i64,
+    pub key: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TombstoneReport {
+    pub dry_run: bool,
+    pub candidates: Vec<TombstoneCandidate>,
+    pub delivered: u64,
+    pub failed: u64,
+    /// Id of the entry in the audit store, not written for dry runs
+    pub audit_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct TombstoneAudit<'a> {
+    action: &'static str,
+    created_at: i64,
+    brokers: Option<&'a str>,
+    topics: Vec<&'a str>,
+    selector: &'a TombstoneSelector,
+    candidates: &'a [TombstoneCandidate],
+    delivered: u64,
+    failed: u64,
+}
+
+/// Find the live keys matching the selector in every partition of the topics and, unless it is a
+/// dry run, produce a tombstone for each of them and record it in the audit store. Nothing is
+/// produced when a scan is cancelled. Every topic is scanned with `onTombstoneProgress-{id}`
+/// progress and the scan is stopped by `offTombstone-{id}`
+pub async fn tombstone_keys(
+    window: Window,
+    common_config: ClientConfig,
+    producer: &FutureProducer,
+    topics: &[(String, TopicDecoders<'_>)],
+    options: TombstoneOptions,
+    audit: &Store,
+    id: String,
+) -> Result<TombstoneReport, String> {
+    let TombstoneOptions {
+        selector, dry_run, ..
+    } = options;
+    if selector.keys.is_none() && selector.filter.is_none() {
+        return Err("Keys or filter are required to select what to erase".into());
+    }
+    let matcher = MessageMatcher::from_filter(selector.filter.clone())?;
+
+    let mut candidates = vec![];
+    let mut raw_keys = vec![];
+    for (topic, decoders) in topics {
+        let readers = create_topic_readers(&common_config, topic, None)?;
+        let latest = LatestRecords::new();
+
+        // Keys are compared as raw bytes, so that tombstones replace exactly the same keys.
+        // A key written to several partitions needs a tombstone in each of them, compaction
+        // only removes the records of the partition the tombstone is written to
+        let progress = scan_topic(
+            &window,
+            &readers,
+            decoders,
+            None,
+            "Tombstone",
+            &id,
+            |message, message_result| {
+                if let Some(raw_key) = message.key() {
+                    latest.insert((message.partition(), raw_key.to_vec()), message_result);
+                }
+                Ok(())
+            },
+        )
+        .await?;
+
+        // The latest records of a partial scan might not be the latest ones of the topic
+        if progress.cancelled {
+            return Err(format!(
+                "Scan of topic: {} was cancelled, no tombstone was produced",
+                topic
+            ));
+        }
+
+        let records = select_live_records(
+            latest.into_records(),
+            selector.keys.as_deref(),
+            matcher.as_ref(),
+        );
+        for (raw_key, message) in records {
+            candidates.push(TombstoneCandidate {
+                topic: topic.clone(),
+                partition: message.partition,
+                offset: message.offset,
+                key: message.key.unwrap_or_default(),
+            });
+            raw_keys.push(raw_key);
+        }
+    }
+
+    if dry_run {
+        return Ok(TombstoneReport {
+            dry_run,
+            candidates,
+            delivered: 0,
+            failed: 0,
+            audit_id: None,
+        });
+    }
+
+    let deliveries = candidates
+        .iter()
+        .zip(raw_keys.iter())
+        .map(|(candidate, raw_key)| {
+            let record: FutureRecord<[u8], [u8]> = FutureRecord::to(&candidate.topic)
+                .key(raw_key.as_slice())
+                .partition(candidate.partition);
+            producer.send(record, Timeout::Never)
+        });
+
+    let mut delivered = 0;
+    let mut failed = 0;
+    for (result, candidate) in join_all(deliveries)
+        .await
+        .into_iter()
+        .zip(candidates.iter())
+    {
+        match result {
+            Ok(_) => delivered += 1,
+            Err((err, _)) => {
+                failed += 1;
+                log::warn!(
+                    "Could not deliver tombstone for key {} of topic {}: {}",
+                    candidate.key,
+                    candidate.topic,
+                    err
+                );
+            }
+        }
+    }
+
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Invalid system time: {}", err.to_string()))?
+        .as_millis() as i64;
+    let entry = TombstoneAudit {
+        action: "Tombstone",
+        created_at,
+        brokers: common_config.get("bootstrap.servers"),
+        topics: topics.iter().map(|(topic, _)| topic.as_str()).collect(),
+        selector: &selector,
+        candidates: &candidates,
+        delivered,
+        failed,
+    };
+    let entry = serde_json::to_value(&entry)
+        .map_err(|err| format!("Could not serialize the audit entry: {}", err.to_string()))?;
+    let audit_id = save_in_store(audit, entry, None)?;
+
+    Ok(TombstoneReport {
+        dry_run,
+        candidates,
+        delivered,
+        failed,
+        audit_id: Some(audit_id),
+    })
+}
+
+/// Latest records of each partition and key still holding a value, sorted by key and partition
+fn select_live_records(
+    latest: HashMap<(i32, Vec<u8>), KafkaMessageResponse>,
+    keys: Option<&[String]>,
+    matcher: Option<&MessageMatcher>,
+) -> Vec<(Vec<u8>, KafkaMessageResponse)> {
+    let mut records: Vec<(Vec<u8>, KafkaMessageResponse)> = latest
+        .into_iter()
+        .filter(|(_, message)| {
+            let is_key_selected = keys.map_or(true, |keys| {
+                message.key.as_ref().map_or(false, |key| keys.contains(key))
+            });
+            let is_matching = matcher.map_or(true, |matcher| message.matches(matcher));
+
+            // Partitions where the key is already erased do not need another tombstone
+            message.value.is_some() && is_key_selected && is_matching
+        })
+        .map(|((_, raw_key), message)| (raw_key, message))
+        .collect();
+    records.sort_by(|(_, a), (_, b)| (&a.key, a.partition).cmp(&(&b.key, b.partition)));
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(
+        partition: i32,
+        offset: i64,
+        key: &str,
+        value: Option<&str>,
+    ) -> KafkaMessageResponse {
+        KafkaMessageResponse {
+            headers: None,
+            value: value.map(str::to_owned),
+            key: Some(key.to_owned()),
+            offset,
+            partition,
+            timestamp: offset,
+            headers_encoding: None,
+            value_encoding: None,
+            key_encoding: None,
+        }
+    }
+
+    fn latest_records(messages: Vec<KafkaMessageResponse>) -> LatestRecords<(i32, Vec<u8>)> {
+        let latest = LatestRecords::new();
+        for message in messages {
+            let raw_key = message.key.clone().unwrap().into_bytes();
+            latest.insert((message.partition, raw_key), message);
+        }
+        latest
+    }
+
+    fn positions(records: &[(Vec<u8>, KafkaMessageResponse)]) -> Vec<(String, i32, i64)> {
+        records
+            .iter()
+            .map(|(_, message)| {
+                (
+                    message.key.clone().unwrap(),
+                    message.partition,
+                    message.offset,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn selects_every_partition_holding_a_live_key() {
+        let latest = latest_records(vec![
+            message(0, 1, "a", Some("old")),
+            message(1, 5, "a", Some("new")),
+            message(0, 2, "b", Some("value")),
+        ]);
+
+        let records = select_live_records(latest.into_records(), None, None);
+
+        assert_eq!(
+            positions(&records),
+            vec![("a".into(), 0, 1), ("a".into(), 1, 5), ("b".into(), 0, 2)]
+        );
+    }
+
+    #[test]
+    fn keeps_live_partitions_of_a_key_erased_elsewhere() {
+        // The newest record of the key is a tombstone, the older partition still has a value
+        let latest = latest_records(vec![
+            message(0, 1, "a", Some("value")),
+            message(1, 5, "a", None),
+            message(0, 2, "b", Some("value")),
+        ]);
+        let keys = vec!["a".to_owned()];
+
+        let records = select_live_records(latest.into_records(), Some(&keys), None);
+
+        assert_eq!(positions(&records), vec![("a".into(), 0, 1)]);
+    }
+}
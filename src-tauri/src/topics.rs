@@ -8,13 +8,14 @@ use rdkafka::{
     admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
     client::DefaultClientContext,
     consumer::{Consumer, StreamConsumer},
-    ClientConfig,
+    ClientConfig, Offset, TopicPartitionList,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tauri::Window;
 use tokio::task::JoinHandle;
 
 use crate::groups::{get_group_offsets, get_groups_without_ours, GroupState};
+use crate::records::{delete_records, DeletedRecords};
 
 #[derive(Serialize, Debug, PartialEq)]
 pub struct TopicResponse {
@@ -33,6 +34,21 @@ pub struct TopicWatermarkResponse {
     pub watermark: usize,
 }
 
+/// Records are deleted up to this point, the record at the offset itself is kept
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(tag = "type")]
+pub enum TruncateBefore {
+    /// Every record, the topic is left empty
+    HighWatermark,
+    Offset {
+        offset: i64,
+    },
+    /// Every record older than the timestamp, in milliseconds
+    Timestamp {
+        timestamp: i64,
+    },
+}
+
 pub async fn create_topic(
     admin: &AdminClient<DefaultClientContext>,
     topic_name: String,
@@ -64,6 +80,119 @@ pub async fn delete_topic(
     Ok(())
 }
 
+/// Delete the records of the topic partitions, all of them when not given, up to an offset or
+/// timestamp. The topic, its configuration and its consumer groups are left as they are
+pub async fn truncate_topic(
+    admin: &AdminClient<DefaultClientContext>,
+    consumer: &StreamConsumer,
+    topic_name: String,
+    partitions: Option<Vec<i32>>,
+    before: TruncateBefore,
+) -> Result<Vec<DeletedRecords>, String> {
+    let metadata = consumer
+        .fetch_metadata(Some(&topic_name), Duration::from_secs(30))
+        .map_err(|err| {
+            format!(
+                "Could not fetch topic metadada for topic: {}\n\nError: {}",
+                topic_name,
+                err.to_string()
+            )
+        })?;
+    let topic_partitions: Vec<i32> = metadata
+        .topics()
+        .first()
+        .unwrap()
+        .partitions()
+        .iter()
+        .map(|partition| partition.id())
+        .collect();
+
+    let partitions = match partitions {
+        Some(partitions) => {
+            // The broker would answer with an error for each of them, after deleting the others
+            if let Some(partition) = partitions
+                .iter()
+                .find(|partition| !topic_partitions.contains(partition))
+            {
+                return Err(format!(
+                    "Partition {} does not exist in topic: {}",
+                    partition, topic_name
+                ));
+            }
+            partitions
+        }
+        None => topic_partitions,
+    };
+
+    let mut before_offsets = TopicPartitionList::new();
+    for partition in &partitions {
+        let offset = match before {
+            TruncateBefore::HighWatermark => Offset::End,
+            TruncateBefore::Offset { offset } => {
+                // Deleting past the high watermark is refused by the broker
+                let (_, high) = consumer
+                    .fetch_watermarks(&topic_name, *partition, Duration::from_secs(30))
+                    .map_err(|err| {
+                        format!(
+                            "Could not fetch watermarks for topic: {}, partition: {}\n\nError: {}",
+                            topic_name,
+                            partition,
+                            err.to_string()
+                        )
+                    })?;
+                Offset::Offset(offset.min(high))
+            }
+            TruncateBefore::Timestamp { timestamp } => Offset::Offset(timestamp),
+        };
+        before_offsets
+            .add_partition_offset(&topic_name, *partition, offset)
+            .map_err(|err| err.to_string())?;
+    }
+
+    // Offsets of the first records at or after the timestamp, none means every record is older
+    if let TruncateBefore::Timestamp { .. } = before {
+        before_offsets = consumer
+            .offsets_for_times(before_offsets, Duration::from_secs(30))
+            .map_err(|err| {
+                format!(
+                    "Could not get offsets for timestamp of topic: {}\n\nError: {}",
+                    topic_name,
+                    err.to_string()
+                )
+            })?;
+        let mut offsets = TopicPartitionList::new();
+        for element in before_offsets.elements() {
+            // A failed lookup must not delete the whole partition
+            element.error().map_err(|err| {
+                format!(
+                    "Could not get offsets for timestamp of topic: {}, partition: {}\n\nError: {}",
+                    topic_name,
+                    element.partition(),
+                    err.to_string()
+                )
+            })?;
+            let offset = match element.offset() {
+                Offset::Offset(offset) => Offset::Offset(offset),
+                Offset::End => Offset::End,
+                offset => {
+                    return Err(format!(
+                        "Unexpected offset {:?} for timestamp of topic: {}, partition: {}",
+                        offset,
+                        topic_name,
+                        element.partition()
+                    ))
+                }
+            };
+            offsets
+                .add_partition_offset(&topic_name, element.partition(), offset)
+                .map_err(|err| err.to_string())?;
+        }
+        before_offsets = offsets;
+    }
+
+    delete_records(admin, &topic_name, &before_offsets, Duration::from_secs(30)).await
+}
+
 pub async fn get_topics(consumer: &StreamConsumer) -> Result<Vec<TopicResponse>, String> {
     let metadata = consumer
         .fetch_metadata(None, Duration::from_secs(30))
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
+use rdkafka::ClientConfig;
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+use crate::messages::{serialize_payload, DeliveryReport, MessageCodecs, MessageFormat};
+use crate::producers::SerializedRecord;
+
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionMessage {
+    pub headers: Option<HashMap<String, Option<String>>>,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub partition: Option<i32>,
+}
+
+/// How the transaction ends once every message is delivered
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum TransactionOutcome {
+    Commit,
+    /// The messages stay in the log but read_committed consumers skip them
+    Abort,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TransactionReport {
+    pub outcome: TransactionOutcome,
+    /// In the same order as the messages
+    pub deliveries: Vec<DeliveryReport>,
+}
+
+/// Producer with its own transactional id, initialized once and reused by the transactions of
+/// the connection. It runs a single transaction at a time
+pub struct TransactionalProducer {
+    producer: FutureProducer,
+}
+
+impl TransactionalProducer {
+    pub async fn new(producer_config: &ClientConfig) -> Result<TransactionalProducer, String> {
+        // A generated id never fences the producer of another window or application
+        let producer: FutureProducer = producer_config
+            .clone()
+            .set(
+                "transactional.id",
+                format!("kafka-panel-{}", Uuid::new_v4()),
+            )
+            .create()
+            .map_err(|err| format!("Error creating transactional producer: {}", err.to_string()))?;
+
+        run_blocking(&producer, |producer| {
+            producer.init_transactions(TRANSACTION_TIMEOUT)
+        })
+        .await
+        .map_err(|err| format!("Error initializing transactions: {}", err))?;
+
+        Ok(TransactionalProducer { producer })
+    }
+}
+
+/// Send the messages atomically, in order, with the transactional producer. The transaction is
+/// aborted whatever the requested outcome when a message cannot be delivered
+pub async fn send_transaction(
+    producer: &TransactionalProducer,
+    topic: String,
+    messages: Vec<TransactionMessage>,
+    key_format: Option<MessageFormat>,
+    value_format: Option<MessageFormat>,
+    outcome: TransactionOutcome,
+    codecs: MessageCodecs<'_>,
+) -> Result<TransactionReport, String> {
+    if messages.is_empty() {
+        return Err("A transaction needs at least one message".into());
+    }
+
+    // Serializing can wait for the schema registry, it is done first so that nothing runs
+    // between two records once the transaction has begun
+    let mut records = vec![];
+    for message in messages {
+        records.push(
+            serialize_record(message, key_format.as_ref(), value_format.as_ref(), codecs).await?,
+        );
+    }
+
+    let producer = &producer.producer;
+    producer
+        .begin_transaction()
+        .map_err(|err| format!("Error beginning the transaction: {}", err.to_string()))?;
+
+    match send_records(producer, &topic, &records).await {
+        Ok(deliveries) => {
+            match outcome {
+                TransactionOutcome::Commit => {
+                    if let Err(err) = run_blocking(producer, |producer| {
+                        producer.commit_transaction(TRANSACTION_TIMEOUT)
+                    })
+                    .await
+                    {
+                        abort_transaction(producer).await;
+                        return Err(format!("Error committing the transaction: {}", err));
+                    }
+                }
+                TransactionOutcome::Abort => run_blocking(producer, |producer| {
+                    producer.abort_transaction(TRANSACTION_TIMEOUT)
+                })
+                .await
+                .map_err(|err| format!("Error aborting the transaction: {}", err))?,
+            }
+
+            Ok(TransactionReport {
+                outcome,
+                deliveries,
+            })
+        }
+        Err(err) => {
+            abort_transaction(producer).await;
+            Err(format!("Transaction aborted: {}", err))
+        }
+    }
+}
+
+async fn serialize_record(
+    message: TransactionMessage,
+    key_format: Option<&MessageFormat>,
+    value_format: Option<&MessageFormat>,
+    codecs: MessageCodecs<'_>,
+) -> Result<SerializedRecord, String> {
+    let key = match message.key {
+        Some(key) => Some(serialize_payload(key, key_format, codecs).await?),
+        None => None,
+    };
+    let value = match message.value {
+        Some(value) => Some(serialize_payload(value, value_format, codecs).await?),
+        None => None,
+    };
+
+    let mut headers = OwnedHeaders::new();
+    for (header_key, header_value) in message.headers.iter().flatten() {
+        headers = headers.insert(Header {
+            key: header_key,
+            value: header_value.as_deref(),
+        });
+    }
+
+    Ok(SerializedRecord {
+        headers,
+        key,
+        value,
+        partition: message.partition,
+    })
+}
+
+/// Every record is enqueued before waiting for the deliveries, in the order of the messages
+async fn send_records(
+    producer: &FutureProducer,
+    topic: &str,
+    records: &[SerializedRecord],
+) -> Result<Vec<DeliveryReport>, String> {
+    let mut deliveries = vec![];
+    for record in records {
+        deliveries.push(enqueue_record(producer, topic, record).await?);
+    }
+
+    join_all(deliveries)
+        .await
+        .into_iter()
+        .map(|delivery| match delivery {
+            Ok(Ok((partition, offset))) => Ok(DeliveryReport {
+                partition,
+                offset,
+                timestamp: None,
+            }),
+            Ok(Err((err, _))) => Err(format!(
+                "Error while sending the message: {}",
+                err.to_string()
+            )),
+            Err(_) => Err("The message delivery was cancelled".to_owned()),
+        })
+        .collect()
+}
+
+async fn enqueue_record(
+    producer: &FutureProducer,
+    topic: &str,
+    record: &SerializedRecord,
+) -> Result<DeliveryFuture, String> {
+    let mut future_record = FutureRecord::to(topic).headers(record.headers.clone());
+    if let Some(ref key) = record.key {
+        future_record = future_record.key(key);
+    }
+    if let Some(ref value) = record.value {
+        future_record = future_record.payload(value);
+    }
+    if let Some(partition) = record.partition {
+        future_record = future_record.partition(partition);
+    }
+
+    loop {
+        match producer.send_result(future_record) {
+            Ok(delivery) => return Ok(delivery),
+            // The same record is retried so that the order is kept
+            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                future_record = returned;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Err((err, _)) => {
+                return Err(format!(
+                    "Error while sending the message: {}",
+                    err.to_string()
+                ))
+            }
+        }
+    }
+}
+
+async fn abort_transaction(producer: &FutureProducer) {
+    if let Err(err) = run_blocking(producer, |producer| {
+        producer.abort_transaction(TRANSACTION_TIMEOUT)
+    })
+    .await
+    {
+        log::warn!("Could not abort the transaction: {}", err);
+    }
+}
+
+/// The transaction calls wait for the coordinator, they must not block the async runtime
+async fn run_blocking<F>(producer: &FutureProducer, call: F) -> Result<(), String>
+where
+    F: FnOnce(&FutureProducer) -> KafkaResult<()> + Send + 'static,
+{
+    let producer = producer.clone();
+    tokio::task::spawn_blocking(move || call(&producer))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
@@ -21,45 +21,69 @@ export const useAutosendsStore = defineStore('autosends', () => {
 			id,
 		};
 
-		// Start seding messages
-		const {remainingTimeObservable, messagesSentObservable, onFinish} = autosendsService.startAutosend(activeAutosend);
+		try {
+			// Start seding messages
+			const tracking = await autosendsService.startAutosend(activeAutosend);
+			pushAutosend(activeAutosend, tracking);
+		} catch (error) {
+			logger.error(`${error}`, {autosendsService});
+			alert?.value?.show({
+				title: 'Error',
+				type: 'error',
+				description: `${error}`
+			});
+		}
+	}
+
+	// Autosends survive the window reload, pick up the ones still running
+	async function restoreAutosends() {
+		const statuses = await autosendsService.getAutosends();
+		for (const status of statuses) {
+			if (autosends.value.some(autosend => autosend.id === status.id)) {
+				continue;
+			}
+
+			const activeAutosend: ActiveAutosend = {
+				...status.autosend,
+				id: status.id,
+			};
+			const tracking = await autosendsService.trackAutosend(status.id, status.ends_at, status.sent);
+			pushAutosend(activeAutosend, tracking);
+		}
+	}
+
+	function pushAutosend(activeAutosend: ActiveAutosend, tracking: Awaited<ReturnType<AutosendsService['trackAutosend']>>) {
+		const {remainingTimeObservable, messagesSentObservable, failuresObservable, onFinish} = tracking;
 
 		activeAutosend.remainingTimeObservable = remainingTimeObservable.pipe(map(time =>  time.toFormat('hh::mm:ss')));
 		activeAutosend.messagesSentObservable = messagesSentObservable;
 
 		autosends.value.push(activeAutosend);
 
-		messagesSentObservable.subscribe({
-			error: async error => {
-				logger.error(error, {autosendsService});
-				alert?.value?.show({
-					title: 'Error',
-					type: 'error',
-					description: error
-				});
-				await stopAutosend(activeAutosend);
-			}
+		failuresObservable.subscribe(failure => {
+			logger.error(`Autosend message ${failure.sequence} to ${activeAutosend.topic} failed: ${failure.error}`, {autosendsService});
 		});
 
 		// On autosend finish remove it from store
 		onFinish(() => {
-			popAutosend(id);
+			popAutosend(activeAutosend.id);
 		});
 	}
 
 	async function stopAutosend(autosend: ActiveAutosend) {
-		// Stop sending messages
-		autosendsService.stopAutosend(autosend);
-
-		// On autosend is stopped remove it from store
-		popAutosend(autosend.id);
+		// Stop sending messages, the autosend is removed from store once finished
+		await autosendsService.stopAutosend(autosend);
 	}
 
 	function popAutosend(id: string) {
 		const index = autosends.value.findIndex(autosendTmp => autosendTmp.id === id);
-		autosends.value.splice(index, 1);
+		if (index !== -1) {
+			autosends.value.splice(index, 1);
+		}
 	}
 
+	restoreAutosends().catch(error => logger.error(`${error}`, {autosendsService}));
+
 	return {
 		autosends,
 		startAutosend,
@@ -9,7 +9,15 @@ export const useConnectionStore = defineStore('connection', () => {
 
 	async function setConnection(newConnection: Connection) {
 		const groupId = `${newConnection.groupPrefix ? `${newConnection.groupPrefix}.` : ''}kafka-panel`;
-		await kafkaService.setConnection(newConnection.brokers, groupId, newConnection.auth);
+		await kafkaService.setConnection(
+			newConnection.brokers,
+			groupId,
+			newConnection.auth,
+			newConnection.partitioner,
+			newConnection.producer,
+			newConnection.schemaRegistry,
+			newConnection.protobuf
+		);
 		connection.value = newConnection;
 	}
 
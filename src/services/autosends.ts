@@ -1,52 +1,90 @@
+import { invoke } from '@tauri-apps/api';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { Duration } from 'luxon';
-import { BehaviorSubject } from 'rxjs';
-import { ActiveAutosend, AutosendTime } from '../types/autosend';
+import { BehaviorSubject, Subject } from 'rxjs';
+import { ActiveAutosend, AutosendFailure, AutosendStatus, AutosendTime } from '../types/autosend';
 import { KafkaService } from './kafka';
 import { Timer } from './Timer';
 
 export class AutosendsService {
-	private intervals: Record<string, NodeJS.Timer> = {};
+	private unlisteners: Record<string, UnlistenFn[]> = {};
 	public readonly kafkaService = new KafkaService();
 
-	startAutosend(autosend: ActiveAutosend) {
-		const messagesCounter = new BehaviorSubject<number>(0);
+	async startAutosend(autosend: ActiveAutosend) {
+		const tracking = await this.trackAutosend(autosend.id, autosend.options.duration ? Date.now() + this.castAutosendTimeToDuration(autosend.options.duration).as('milliseconds') : null);
 
-		const duration = this.castAutosendTimeToDuration(autosend.options.duration);
-		const timer = new Timer(duration);
-		timer.start();
-		timer.onFinish(() => {
-			this.stopAutosend(autosend);
-		});
-
-		let messagesSent = 0;
-
-		const interval = this.castAutosendTimeToDuration(autosend.options.interval);
-		this.intervals[autosend.id] = setInterval((autosend: ActiveAutosend) => {
-			try {
-				this.kafkaService.sendMessage(autosend.topic, {
+		try {
+			await invoke('start_autosend_command', {
+				autosend: {
+					topic: autosend.topic,
 					headers: autosend.headers,
 					key: autosend.key,
-					value: autosend.value
-				})
-					.then(() => {
-						messagesSent += 1;
-						messagesCounter.next(messagesSent);
-					});
-			} catch (error) {
-				messagesCounter.error(error);
-				this.stopAutosend(autosend);
-			}
-		}, interval.as('milliseconds'), autosend);
+					value: autosend.value,
+					options: autosend.options
+				},
+				id: autosend.id
+			});
+		} catch (error) {
+			this.unlisten(autosend.id);
+			throw error;
+		}
+
+		return tracking;
+	}
+
+	// Autosends keep running in the backend when the window is reloaded
+	async getAutosends() {
+		return invoke<AutosendStatus[]>('get_autosends_command');
+	}
+
+	async trackAutosend(id: string, endsAt: number | null, messagesSent = 0) {
+		const messagesCounter = new BehaviorSubject<number>(messagesSent);
+		const failures = new Subject<AutosendFailure>();
+		const finishCallbacks: (() => unknown)[] = [];
+
+		const timer = new Timer(Duration.fromMillis(endsAt !== null ? Math.max(endsAt - Date.now(), 0) : 0));
+		if (endsAt !== null) {
+			timer.start();
+		}
+
+		this.unlisteners[id] = [
+			await listen<AutosendStatus>(`onAutosend-${id}`, (event) => {
+				messagesCounter.next(event.payload.sent);
+
+				if (event.payload.finished) {
+					timer.stop();
+					this.unlisten(id);
+					messagesCounter.complete();
+					failures.complete();
+					for (const callback of finishCallbacks) {
+						callback();
+					}
+				}
+			}),
+			await listen<AutosendFailure>(`onAutosendFailure-${id}`, (event) => {
+				failures.next(event.payload);
+			}),
+		];
 
 		return {
 			messagesSentObservable: messagesCounter.asObservable(),
+			failuresObservable: failures.asObservable(),
 			remainingTimeObservable: timer.remainingObservable,
-			onFinish: timer.onFinish.bind(timer)
+			onFinish: (callback: () => unknown) => {
+				finishCallbacks.push(callback);
+			}
 		};
 	}
 
-	stopAutosend(autosend: ActiveAutosend) {
-		clearInterval(this.intervals[autosend.id]);
+	async stopAutosend(autosend: ActiveAutosend) {
+		await invoke('stop_autosend_command', {id: autosend.id});
+	}
+
+	private unlisten(id: string) {
+		for (const unlisten of this.unlisteners[id] ?? []) {
+			unlisten();
+		}
+		delete this.unlisteners[id];
 	}
 
 	private castAutosendTimeToDuration(time: AutosendTime) {
@@ -3,10 +3,10 @@ import { invoke } from '@tauri-apps/api';
 import { emit, listen, UnlistenFn } from '@tauri-apps/api/event';
 import { clone } from 'ramda';
 import { Observable, Subject } from 'rxjs';
-import { SaslConfig } from '../types/connection';
+import { Connection, Partitioner, ProducerOptions, ProtobufDescriptorConfig, SaslConfig, SchemaRegistryConfig } from '../types/connection';
 import { ConsumerGroup, ConsumerGroupState } from '../types/consumerGroup';
-import { Message, MessageContent } from '../types/message';
-import { Topic } from '../types/topic';
+import { CopyOptions, CopyProgress, DeliveryReport, ExportFormat, FilterProgress, ImportOptions, ImportProgress, ListenOptions, MaterializedExport, MaterializedTopic, Message, MessageContent, MessageFilter, MessageFormat, MessagesPage, PartitionCursor, PartitionRange, OutgoingMessage, PayloadEncoding, RoundTrip, OffsetGap, SearchProgress, TombstoneReport, TombstoneSelector, TransactionOutcome, TransactionReport } from '../types/message';
+import { DeletedRecords, Topic, TruncateBefore } from '../types/topic';
 import { v4 as uuidv4 } from 'uuid';
 import { tryJsonParse } from './utils';
 
@@ -22,8 +22,18 @@ export type AsyncSubject<T> = {
 export class KafkaService {
 	public readonly id = uuidv4();
 
-	async setConnection(brokers: string[], groupId: string, sasl?: SaslConfig) {
-		await invoke('set_connection_command', {brokers, groupId, sasl});
+	async setConnection(
+		brokers: string[],
+		groupId: string,
+		sasl?: SaslConfig,
+		partitioner?: Partitioner,
+		producer?: ProducerOptions,
+		schemaRegistry?: SchemaRegistryConfig,
+		protobuf?: ProtobufDescriptorConfig[]
+	) {
+		await invoke('set_connection_command', {
+			connection: {brokers, group_id: groupId, sasl, partitioner, producer, schema_registry: schemaRegistry, protobuf},
+		});
 	}
 
 	async getTopicsState() {
@@ -100,14 +110,32 @@ export class KafkaService {
 		});
 	}
 
-	async listenMessages(topic: string, messagesNumber: number): Promise<AsyncSubject<Message>> {
+	/**
+	 * Delete the records of the partitions, all of them when not given, keeping the topic
+	 */
+	truncateTopic(name: string, before: TruncateBefore, partitions?: number[]) {
+		return invoke<DeletedRecords[]>('truncate_topic_command', {
+			topicName: name,
+			partitions,
+			before,
+		});
+	}
+
+	async listenMessages(topic: string, options: ListenOptions, onProgress?: (progress: FilterProgress) => void, onOffsetGap?: (gap: OffsetGap) => void): Promise<AsyncSubject<Message>> {
 		const messagesSubject = new Subject<Message>();
 
 		let unlisten: UnlistenFn | undefined = await listen<Message>(`onMessage-${this.id}`, (event) => {
 			messagesSubject.next(event.payload);
 		});
+		let unlistenProgress: UnlistenFn | undefined = await listen<FilterProgress>(`onProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+		// Only emitted with read_committed, for offsets without a record between two messages
+		let unlistenGap: UnlistenFn | undefined = await listen<OffsetGap>(`onOffsetGap-${this.id}`, (event) => {
+			onOffsetGap?.(event.payload);
+		});
 
-		const listenMessagesCommand = invoke('listen_messages_command', {topic, messagesNumber, id: this.id})
+		const listenMessagesCommand = invoke('listen_messages_command', {topic, options, id: this.id})
 			.then(() => {
 				messagesSubject.complete();
 			})
@@ -117,6 +145,10 @@ export class KafkaService {
 			.finally(() => {
 				unlisten?.();
 				unlisten = undefined;
+				unlistenProgress?.();
+				unlistenProgress = undefined;
+				unlistenGap?.();
+				unlistenGap = undefined;
 			});
 		const unsubscribe = async () => {
 			await emit(`offMessage-${this.id}`);
@@ -129,19 +161,200 @@ export class KafkaService {
 		};
 	}
 
-	async sendMessage(topic: string, message: MessageContent) {
-		const interpolatedHeaders = this.interpolateFakeValues(clone(message.headers), {faker});
-		const interpolatedKey = this.interpolateFakeValues(clone(message.key), {faker});
-		const interpolatedValue = this.interpolateFakeValues(clone(message.value), {faker, key: tryJsonParse(interpolatedKey)});
+	// Older messages page by page, give back the cursors of a page to get the one before it
+	async getMessagesPage(topic: string, pageSize: number, cursors?: PartitionCursor[], encoding?: PayloadEncoding) {
+		return invoke<MessagesPage>('get_messages_page_command', {topic, cursors, pageSize, encoding});
+	}
 
-		await invoke('send_message_command', {
+	async searchMessages(topic: string, filter: MessageFilter, encoding?: PayloadEncoding, onProgress?: (progress: SearchProgress) => void): Promise<AsyncSubject<Message>> {
+		const messagesSubject = new Subject<Message>();
+
+		let unlisten: UnlistenFn | undefined = await listen<Message>(`onSearchMessage-${this.id}`, (event) => {
+			messagesSubject.next(event.payload);
+		});
+		let unlistenProgress: UnlistenFn | undefined = await listen<SearchProgress>(`onSearchProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		const searchMessagesCommand = invoke('search_messages_command', {topic, filter, encoding, id: this.id})
+			.then(() => {
+				messagesSubject.complete();
+			})
+			.catch(async error => {
+				messagesSubject.error(error);
+			})
+			.finally(() => {
+				unlisten?.();
+				unlisten = undefined;
+				unlistenProgress?.();
+				unlistenProgress = undefined;
+			});
+		const unsubscribe = async () => {
+			await emit(`offSearch-${this.id}`);
+			await searchMessagesCommand;
+		};
+
+		return {
+			subscribe: messagesSubject.subscribe.bind(messagesSubject),
+			unsubscribe
+		};
+	}
+
+	async exportMessages(topic: string, path: string, format: ExportFormat, ranges?: PartitionRange[], filter?: MessageFilter, encoding?: PayloadEncoding, onProgress?: (progress: SearchProgress) => void) {
+		let unlistenProgress: UnlistenFn | undefined = await listen<SearchProgress>(`onExportProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		try {
+			return await invoke<SearchProgress>('export_messages_command', {topic, options: {path, format, ranges, filter, encoding}, id: this.id});
+		} finally {
+			unlistenProgress?.();
+			unlistenProgress = undefined;
+		}
+	}
+
+	async stopExportMessages() {
+		await emit(`offExport-${this.id}`);
+	}
+
+	async materializeTopic(topic: string, filter?: MessageFilter, exportTo?: MaterializedExport, encoding?: PayloadEncoding, onProgress?: (progress: SearchProgress) => void) {
+		let unlistenProgress: UnlistenFn | undefined = await listen<SearchProgress>(`onMaterializeProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		try {
+			return await invoke<MaterializedTopic>('materialize_topic_command', {topic, options: {filter, encoding, export: exportTo}, id: this.id});
+		} finally {
+			unlistenProgress?.();
+			unlistenProgress = undefined;
+		}
+	}
+
+	async stopMaterializeTopic() {
+		await emit(`offMaterialize-${this.id}`);
+	}
+
+	// Run it with dryRun first to review the keys that would be erased
+	async tombstoneKeys(topics: string[], selector: TombstoneSelector, dryRun: boolean, encoding?: PayloadEncoding, onProgress?: (progress: SearchProgress) => void) {
+		let unlistenProgress: UnlistenFn | undefined = await listen<SearchProgress>(`onTombstoneProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		try {
+			return await invoke<TombstoneReport>('tombstone_keys_command', {topics, options: {selector, dry_run: dryRun, encoding}, id: this.id});
+		} finally {
+			unlistenProgress?.();
+			unlistenProgress = undefined;
+		}
+	}
+
+	async stopTombstoneKeys() {
+		await emit(`offTombstone-${this.id}`);
+	}
+
+	async importMessages(topic: string, path: string, options: ImportOptions, onProgress?: (progress: ImportProgress) => void) {
+		let unlistenProgress: UnlistenFn | undefined = await listen<ImportProgress>(`onImportProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		try {
+			return await invoke<ImportProgress>('import_messages_command', {topic, path, options, id: this.id});
+		} finally {
+			unlistenProgress?.();
+			unlistenProgress = undefined;
+		}
+	}
+
+	async stopImportMessages() {
+		await emit(`offImport-${this.id}`);
+	}
+
+	async copyMessages(topic: string, targetTopic: string, options: CopyOptions, targetConnection?: Connection, ranges?: PartitionRange[], filter?: MessageFilter, onProgress?: (progress: SearchProgress) => void) {
+		let unlistenProgress: UnlistenFn | undefined = await listen<SearchProgress>(`onCopyProgress-${this.id}`, (event) => {
+			onProgress?.(event.payload);
+		});
+
+		const target = targetConnection ? {
+			brokers: targetConnection.brokers,
+			group_id: `${targetConnection.groupPrefix ? `${targetConnection.groupPrefix}.` : ''}kafka-panel`,
+			sasl: targetConnection.auth,
+			partitioner: targetConnection.partitioner,
+			producer: targetConnection.producer,
+		} : undefined;
+
+		try {
+			return await invoke<CopyProgress>('copy_messages_command', {
+				topic,
+				targetConnection: target,
+				options: {...options, target_topic: targetTopic, ranges, filter},
+				id: this.id,
+			});
+		} finally {
+			unlistenProgress?.();
+			unlistenProgress = undefined;
+		}
+	}
+
+	async stopCopyMessages() {
+		await emit(`offCopy-${this.id}`);
+	}
+
+	async sendMessage(topic: string, message: MessageContent, keyFormat?: MessageFormat, valueFormat?: MessageFormat, partition?: number, producerOptions?: ProducerOptions) {
+		const interpolated = this.interpolateMessage(message);
+
+		return invoke<DeliveryReport>('send_message_command', {
+			message: {
+				topic,
+				...interpolated,
+				key_format: keyFormat,
+				value_format: valueFormat,
+				partition,
+			},
+			producerOptions,
+		});
+	}
+
+	async sendTransaction(topic: string, messages: (MessageContent & {partition?: number})[], outcome: TransactionOutcome, keyFormat?: MessageFormat, valueFormat?: MessageFormat) {
+		const interpolatedMessages = messages.map(message => ({
+			...this.interpolateMessage(message),
+			partition: message.partition,
+		}));
+
+		return invoke<TransactionReport>('send_transaction_command', {
 			topic,
-			headers: interpolatedHeaders,
-			key: interpolatedKey,
-			value: interpolatedValue,
+			messages: interpolatedMessages,
+			keyFormat,
+			valueFormat,
+			outcome,
+		});
+	}
+
+	/**
+	 * Send the request with a generated correlation id header and wait for the matching reply
+	 */
+	async requestReply(request: OutgoingMessage, replyTopic: string, timeoutMs: number, correlationHeader?: string, encoding?: PayloadEncoding) {
+		return invoke<RoundTrip>('request_reply_command', {
+			request: {
+				...request,
+				...this.interpolateMessage(request),
+			},
+			options: {
+				reply_topic: replyTopic,
+				correlation_header: correlationHeader,
+				timeout_ms: timeoutMs,
+				encoding,
+			},
 		});
 	}
 
+	private interpolateMessage(message: MessageContent) {
+		const headers = this.interpolateFakeValues(clone(message.headers), {faker});
+		const key = this.interpolateFakeValues(clone(message.key), {faker});
+		const value = this.interpolateFakeValues(clone(message.value), {faker, key: tryJsonParse(key)});
+
+		return {headers, key, value};
+	}
+
 	private interpolateFakeValues<T = unknown>(input: T, context: Record<string, unknown>): T {
 		if (typeof input === 'object') {
 			if (Array.isArray(input)) {
@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api';
-import { StorageMessage } from '../types/message';
+import { StorageMessage, TombstoneAudit } from '../types/message';
 import { SettingKey } from '../types/settings';
 
 class Store<T = unknown, K = string> {
@@ -26,7 +26,8 @@ class Store<T = unknown, K = string> {
 
 const storageService = {
 	settings: new Store<unknown, SettingKey>('settings'),
-	messages: new Store<StorageMessage>('messages')
+	messages: new Store<StorageMessage>('messages'),
+	audit: new Store<TombstoneAudit>('audit')
 };
 
 export default storageService;
\ No newline at end of file
@@ -14,10 +14,27 @@ export type ActiveAutosend = Autosend & {
 
 export type AutosendOptions = {
   duration: AutosendTime,
-  interval: AutosendTime
+  interval: AutosendTime,
+  max_messages?: number,
+  rate?: 'FixedRate' | 'FixedDelay'
 }
 
 export type AutosendTime = {
   time_unit: 'Hours'| 'Minutes'| 'Seconds'| 'Milliseconds'
   value: number
-}
\ No newline at end of file
+}
+
+export type AutosendStatus = {
+  id: string,
+  autosend: Autosend,
+  sent: number,
+  failed: number,
+  started_at: number,
+  ends_at: number | null,
+  finished: boolean
+}
+
+export type AutosendFailure = {
+  sequence: number,
+  error: string
+}
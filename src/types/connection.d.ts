@@ -2,11 +2,37 @@ export type Connection = {
   name: string,
   brokers: string[],
   auth?: SaslConfig,
-  groupPrefix?: string
+  groupPrefix?: string,
+  partitioner?: Partitioner,
+  producer?: ProducerOptions,
+  schemaRegistry?: SchemaRegistryConfig,
+  protobuf?: ProtobufDescriptorConfig[]
 }
 
 export type SaslConfig = {
 	mechanism: 'PLAIN' | 'SCRAM-SHA-256' | 'SCRAM_SHA_512'
 	username: string
 	password: string
+}
+
+export type Partitioner = 'ConsistentRandom' | 'Murmur2Random' | 'Fnv1a'
+
+export type Acks = 'None' | 'Leader' | 'All'
+
+export type ProducerOptions = {
+	idempotence?: boolean
+	acks?: Acks
+}
+
+export type SchemaRegistryConfig = {
+	url: string
+	username?: string
+	password?: string
+}
+
+export type ProtobufDescriptorConfig = {
+	path: string
+	topic?: string
+	key_message?: string
+	value_message?: string
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ export type Value = string | null
 export type Headers = Record<Key, Value> | null
 
 export type MessageKeyValue = {
-  key: Key
+  key: Key | null
   value: Value
 }
 
@@ -11,10 +11,220 @@ export type MessageContent = MessageKeyValue & {
   headers: Headers | null
 }
 
+/**
+ * How fields without a decoder are turned into text when listening
+ */
+export type PayloadEncoding = 'Utf8' | 'Base64' | 'Hex' | 'Lossy'
+
+/**
+ * How a field was actually turned into text
+ */
+export type AppliedEncoding = PayloadEncoding | 'Avro' | 'Protobuf' | 'Json' | 'Int8' | 'Int16' | 'Int32' | 'Int64' | 'Uuid'
+
+export type MessageFormat = {
+  type: 'Avro'
+  subject: string
+  version?: number
+} | {
+  type: 'Protobuf'
+  message: string
+  schema_id?: number
+}
+
+export type MessageFilter = {
+  key_pattern?: string
+  headers?: {
+    name: string
+    value?: string
+  }[]
+  value_contains?: string
+  value_json_path?: string
+}
+
+export type FilterProgress = {
+  scanned: number
+  matched: number
+}
+
+export type SearchProgress = {
+  scanned: number
+  total: number
+  matched: number
+  percentage: number
+  /**
+   * Only for the scans producing what they read, like copies
+   */
+  delivered?: number
+  failed?: number
+  /**
+   * Stopped before reading every partition to its end
+   */
+  cancelled: boolean
+}
+
+export type ExportFormat = 'Jsonl' | 'Csv'
+
+export type MaterializedExport = {
+  path: string
+  format: ExportFormat
+}
+
+export type MaterializedTopic = {
+  records: Message[]
+  keys: number
+  tombstones: number
+  keyless: number
+  /**
+   * The scan was cancelled, the records are not exported
+   */
+  partial: boolean
+  progress: SearchProgress
+}
+
+export type TombstoneSelector = {
+  keys?: string[]
+  filter?: MessageFilter
+}
+
+export type TombstoneCandidate = {
+  topic: string
+  partition: number
+  offset: number
+  key: string
+}
+
+export type TombstoneReport = {
+  dry_run: boolean
+  candidates: TombstoneCandidate[]
+  delivered: number
+  failed: number
+  audit_id: string | null
+}
+
+export type TombstoneAudit = {
+  action: 'Tombstone'
+  created_at: number
+  brokers: string | null
+  topics: string[]
+  selector: TombstoneSelector
+  candidates: TombstoneCandidate[]
+  delivered: number
+  failed: number
+}
+
+export type ImportOptions = {
+  keep_partition: boolean
+  key?: { type: 'Keep' } | { type: 'Remove' } | { type: 'Constant', key: string } | { type: 'ValueField', pointer: string }
+  headers?: { type: 'Keep' } | { type: 'Merge' | 'Replace', headers: Record<string, string | null> }
+  key_format?: MessageFormat
+  value_format?: MessageFormat
+}
+
+export type ImportProgress = {
+  read: number
+  delivered: number
+  failed: number
+}
+
+export type CopyOptions = {
+  keep_partition: boolean
+  keep_timestamps: boolean
+  keep_headers: boolean
+}
+
+export type CopyProgress = {
+  scanned: number
+  matched: number
+  delivered: number
+  failed: number
+}
+
+export type DeliveryReport = {
+  partition: number
+  offset: number
+  /**
+   * Timestamp stored by the broker, only reported for single messages
+   */
+  timestamp: number | null
+}
+
+export type TransactionOutcome = 'Commit' | 'Abort'
+
+export type TransactionReport = {
+  outcome: TransactionOutcome
+  deliveries: DeliveryReport[]
+}
+
+export type OutgoingMessage = MessageContent & {
+  topic: string
+  key_format?: MessageFormat
+  value_format?: MessageFormat
+  partition?: number
+}
+
+export type RoundTrip = {
+  correlation_id: string
+  request: DeliveryReport
+  /**
+   * Null when no reply arrived before the timeout
+   */
+  reply: Message | null
+  /**
+   * Milliseconds between sending the request and receiving the reply
+   */
+  latency: number | null
+}
+
+export type PartitionRange = {
+  partition: number
+  start_offset?: number
+  end_offset?: number
+}
+
+export type PartitionCursor = {
+  partition: number
+  offset: number
+}
+
+export type MessagesPage = {
+  messages: Message[]
+  cursors: PartitionCursor[]
+  has_more: boolean
+}
+
+export type IsolationLevel = 'ReadUncommitted' | 'ReadCommitted'
+
+export type ListenOptions = {
+  /**
+   * Last messages of every partition to start from
+   */
+  messages_number: number
+  /**
+   * Start from the first message at or after this timestamp instead of the last messages
+   */
+  from_timestamp?: number
+  ranges?: PartitionRange[]
+  filter?: MessageFilter
+  isolation_level?: IsolationLevel
+  encoding?: PayloadEncoding
+}
+
+/**
+ * Offsets without a record between two records of a read_committed listen
+ */
+export type OffsetGap = {
+  partition: number
+  start_offset: number
+  end_offset: number
+}
+
 export type Message = MessageContent & {
   timestamp: number
   offset: number
   partition: number
+  headers_encoding: Record<Key, AppliedEncoding> | null
+  value_encoding: AppliedEncoding | null
+  key_encoding: AppliedEncoding | null
 }
 
 export type StorageMessage = MessageContent & {
@@ -6,4 +6,4 @@ export type Setting = {
   type: 'text' | 'password' | 'json'
 }
 
-export type SettingKey = 'CONNECTIONS' | 'MESSAGES'
\ No newline at end of file
+export type SettingKey = 'CONNECTIONS' | 'MESSAGES' | 'DECODERS'
\ No newline at end of file
@@ -1,4 +1,18 @@
 export type Topic = {
   name: string
   partitions: number
-}
\ No newline at end of file
+}
+
+/**
+ * Records are deleted up to this point, the record at the offset itself is kept
+ */
+export type TruncateBefore =
+  | { type: 'HighWatermark' }
+  | { type: 'Offset', offset: number }
+  | { type: 'Timestamp', timestamp: number }
+
+export type DeletedRecords = {
+  partition: number
+  low_watermark: number | null
+  error: string | null
+}